@@ -1,90 +1,194 @@
-use crate::query::{Query, parse_words};
+use crate::filter::LineFilter;
+use std::collections::VecDeque;
+use std::fs::File;
 use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 
+/// Log line with context lines, context lines are paired with their offsets
 pub struct LogLine {
     pub line: String,
-    pub before: Vec<String>,
-    pub after: Vec<String>,
+    pub before: Vec<(u64, String)>,
+    pub after: Vec<(u64, String)>,
+    /// Offset after last line of context (or of line itself)
+    pub end_offset: u64,
 }
 
-impl LogLine {
+/// Distance to next requested line up to which log is read sequentially instead of seeking
+const MAX_SKIP: u64 = 64 * 1024;
+const BACKWARD_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Reads lines with context from one open log file. Lines requested in offset order
+/// are read in one sequential pass, recently read lines are reused as context of next ones.
+pub struct LogReader {
+    reader: BufReader<File>,
+    /// Offset of next line to read
+    offset: u64,
+    /// Consecutive lines read last with their offsets, the last one ends at `offset`
+    recent: VecDeque<(u64, String)>,
+}
+
+impl LogReader {
+    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+            offset: 0,
+            recent: VecDeque::new(),
+        })
+    }
+
+    /// Returns line at `offset` with context if it passes `filters`
     pub fn read(
-        path: impl AsRef<Path>,
+        &mut self,
         offset: u64,
         before: usize,
         after: usize,
-        check_order: Option<&Query>,
-    ) -> anyhow::Result<Option<Self>> {
-        let file = std::fs::File::open(path)?;
-        let mut reader = BufReader::new(&file);
-
-        reader.seek(SeekFrom::Start(offset))?;
-        let mut target_line = String::new();
-        reader.read_line(&mut target_line)?;
-        if let Some(query) = check_order {
-            if !query.check_words_order(&parse_words(&target_line)) {
-                return Ok(None);
-            }
+        filters: &[LineFilter],
+    ) -> anyhow::Result<Option<LogLine>> {
+        let first_offset = self.recent.front().map_or(self.offset, |(x, _)| *x);
+        if offset < first_offset || offset > self.offset + MAX_SKIP {
+            self.seek_context(offset, before)?;
         }
+        while self.offset <= offset && self.read_line()? {}
+        let Ok(mut index) = self.recent.binary_search_by_key(&offset, |(x, _)| *x) else {
+            return Ok(None);
+        };
+        // Lines above were dropped by previous read needing less context
+        if index < before && self.recent[0].0 > 0 {
+            self.seek_context(offset, before)?;
+            while self.offset <= offset && self.read_line()? {}
+            index = self.recent.len() - 1;
+        }
+        // Lines before context of this line are not needed for next lines
+        let context_index = index.saturating_sub(before);
+        if !filters
+            .iter()
+            .all(|filter| filter.check(&self.recent[index].1))
+        {
+            self.recent.drain(..context_index);
+            return Ok(None);
+        }
+        while self.recent.len() <= index + after && self.read_line()? {}
+        let line = LogLine {
+            line: self.recent[index].1.clone(),
+            before: self.recent.range(context_index..index).cloned().collect(),
+            after: self
+                .recent
+                .range(index + 1..)
+                .take(after)
+                .cloned()
+                .collect(),
+            end_offset: self
+                .recent
+                .get(index + after + 1)
+                .map_or(self.offset, |(x, _)| *x),
+        };
+        self.recent.drain(..context_index);
+        Ok(Some(line))
+    }
 
-        let lines_before = read_lines_before(&file, offset, before)?;
+    /// Moves to start of `before` lines above line at `offset`, recent lines are dropped
+    fn seek_context(&mut self, offset: u64, before: usize) -> anyhow::Result<()> {
+        let context_start = self.context_start(offset, before)?;
+        self.reader.seek(SeekFrom::Start(context_start))?;
+        self.offset = context_start;
+        self.recent.clear();
+        Ok(())
+    }
 
-        let mut lines_after = Vec::new();
-        for _ in 0..after {
-            let mut line = String::new();
-            if reader.read_line(&mut line)? == 0 {
-                break;
-            }
-            lines_after.push(line.trim_end().to_string());
+    fn read_line(&mut self) -> anyhow::Result<bool> {
+        let mut line = String::new();
+        let len = self.reader.read_line(&mut line)? as u64;
+        if len == 0 {
+            return Ok(false);
         }
+        self.recent
+            .push_back((self.offset, line.trim_end().to_string()));
+        self.offset += len;
+        Ok(true)
+    }
 
-        Ok(Some(Self {
-            before: lines_before,
-            line: target_line.trim_end().to_string(),
-            after: lines_after,
-        }))
+    /// Offset of line `before` lines above line at `offset`, file is scanned backward in chunks
+    fn context_start(&mut self, offset: u64, before: usize) -> anyhow::Result<u64> {
+        if before == 0 {
+            return Ok(offset);
+        }
+        let file = self.reader.get_mut();
+        let mut newlines = 0;
+        let mut chunk_end = offset;
+        let mut buf = Vec::new();
+        while chunk_end > 0 {
+            let chunk_start = chunk_end.saturating_sub(BACKWARD_CHUNK_SIZE);
+            buf.resize((chunk_end - chunk_start) as usize, 0);
+            file.seek(SeekFrom::Start(chunk_start))?;
+            file.read_exact(&mut buf)?;
+            for (i, &b) in buf.iter().enumerate().rev() {
+                if b == b'\n' {
+                    // First newline found ends line just above `offset`
+                    if newlines == before {
+                        return Ok(chunk_start + i as u64 + 1);
+                    }
+                    newlines += 1;
+                }
+            }
+            chunk_end = chunk_start;
+        }
+        Ok(0)
     }
 }
 
-fn read_lines_before(
-    file: &std::fs::File,
+/// Counts lines up to requested offsets, offsets have to grow
+pub struct LineNumbers {
+    reader: BufReader<File>,
     offset: u64,
-    before: usize,
-) -> std::io::Result<Vec<String>> {
-    if before == 0 {
-        return Ok(Vec::new());
-    }
-    let mut result = Vec::new();
-    let mut cursor = file.try_clone()?;
-    let mut pos = offset;
-    let mut buf = Vec::new();
-
-    while pos > 0 && result.len() < before {
-        let step = pos.min(1024);
-        pos -= step;
-        cursor.seek(SeekFrom::Start(pos))?;
+    newlines: u64,
+}
 
-        buf.resize(step as usize, 0);
-        cursor.read_exact(&mut buf)?;
+impl LineNumbers {
+    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+            offset: 0,
+            newlines: 0,
+        })
+    }
 
-        for (i, b) in buf.iter().enumerate().rev() {
-            if *b == b'\n' {
-                let line_start = pos + i as u64 + 1;
-                if line_start < offset {
-                    let mut r = BufReader::new(file.try_clone()?);
-                    r.seek(SeekFrom::Start(line_start))?;
-                    let mut line = String::new();
-                    r.read_line(&mut line)?;
-                    result.push(line.trim_end().to_string());
-                    if result.len() == before {
-                        break;
-                    }
-                }
+    /// Returns 1-based number of line starting at `offset`, None if offset is before previous one
+    pub fn line_number(&mut self, offset: u64) -> anyhow::Result<Option<u64>> {
+        if offset < self.offset {
+            return Ok(None);
+        }
+        while self.offset < offset {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
             }
+            let len = buf.len().min((offset - self.offset) as usize);
+            self.newlines += buf[..len].iter().filter(|&&b| b == b'\n').count() as u64;
+            self.reader.consume(len);
+            self.offset += len as u64;
         }
+        Ok(Some(self.newlines + 1))
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    result.reverse();
-    Ok(result)
+    #[test]
+    fn context_is_read_again_after_read_without_context() {
+        let path = std::env::temp_dir().join(format!("qx-log-reader-{}.log", std::process::id()));
+        std::fs::write(&path, "one\ntwo\nthree\nfour\n").unwrap();
+        let mut reader = LogReader::new(&path).unwrap();
+        let line = reader.read(8, 0, 0, &[]).unwrap().unwrap();
+        assert_eq!(line.line, "three");
+        let line = reader.read(8, 2, 1, &[]).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            line.before,
+            vec![(0, "one".to_string()), (4, "two".to_string())]
+        );
+        assert_eq!(line.after, vec![(14, "four".to_string())]);
+        assert_eq!(line.end_offset, 19);
+    }
 }
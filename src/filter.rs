@@ -0,0 +1,71 @@
+use crate::query::{Query, QueryOptions, parse_words};
+use crate::time_utils::parse_line_timestamp;
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+/// Verifies line read from the log, index can only select candidates for some queries.
+pub enum LineFilter<'a> {
+    WordsOrder(&'a Query),
+    /// Query with phrases or NEAR evaluated against line when index has no positions
+    Proximity(&'a Query, QueryOptions),
+    Substring(String),
+    Regex(Regex),
+    /// Lines with timestamp within `[since, until)`
+    TimeRange(Option<i64>, Option<i64>),
+    /// Lines not matching query (if any) together with filters verifying it
+    Inverted(Option<&'a Query>, QueryOptions, Vec<LineFilter<'a>>),
+}
+
+impl<'a> LineFilter<'a> {
+    pub fn with_substring(substring: &str) -> Self {
+        Self::Substring(substring.to_lowercase())
+    }
+
+    pub fn check(&self, line: &str) -> bool {
+        match self {
+            Self::WordsOrder(query) => query.check_words_order(&parse_words(line)),
+            // Whole query is evaluated, phrases can be anywhere in it (like under OR or NOT)
+            Self::Proximity(query, options) => query.check_line(&parse_words(line), *options),
+            Self::Substring(substring) => line.to_lowercase().contains(substring),
+            Self::Regex(regex) => regex.is_match(line),
+            Self::TimeRange(since, until) => parse_line_timestamp(line).is_some_and(|timestamp| {
+                since.is_none_or(|since| timestamp >= since)
+                    && until.is_none_or(|until| timestamp < until)
+            }),
+            Self::Inverted(query, options, filters) => {
+                let matched = query
+                    .is_none_or(|query| query.check_line(&parse_words(line), *options))
+                    && filters.iter().all(|filter| filter.check(line));
+                !matched
+            }
+        }
+    }
+
+    /// Byte ranges of line parts matched by filter
+    pub fn highlights(&self, line: &str) -> Vec<Range<usize>> {
+        match self {
+            Self::Regex(regex) => {
+                // Highlight capture groups if any, otherwise whole matches
+                let skip = usize::from(regex.captures_len() > 1);
+                regex
+                    .captures_iter(line)
+                    .flat_map(|captures| {
+                        captures
+                            .iter()
+                            .skip(skip)
+                            .flatten()
+                            .map(|m| m.range())
+                            .collect::<Vec<_>>()
+                    })
+                    .filter(|m| !m.is_empty())
+                    .collect()
+            }
+            Self::Substring(substring) => RegexBuilder::new(&regex::escape(substring))
+                .case_insensitive(true)
+                .build()
+                .map(|regex| regex.find_iter(line).map(|m| m.range()).collect())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+}
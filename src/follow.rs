@@ -0,0 +1,79 @@
+use crate::index::{IxBuilder, IxReader};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How often index is rewritten with appended lines
+const INDEX_WRITE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Passes lines appended to log after indexed part to `on_line` until interrupted,
+/// appended lines are added to index. Handles log rotation by rename or truncate.
+pub fn follow_log(
+    log_path: &Path,
+    ix: &IxReader,
+    mut on_line: impl FnMut(u64, &str) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut ix_builder = IxBuilder::from_reader(ix)?;
+    let mut offset = ix.log_size;
+    let mut reader = open_at(log_path, offset)?;
+    let mut line = String::new();
+    let mut index_changed = false;
+    let mut last_index_write = Instant::now();
+    loop {
+        // Incomplete line is kept in `line` until rest of it is written
+        if reader.read_line(&mut line)? > 0 {
+            if line.ends_with('\n') {
+                ix_builder.add_line(&line, offset);
+                on_line(offset, line.trim_end())?;
+                offset += line.len() as u64;
+                line.clear();
+                index_changed = true;
+            }
+            continue;
+        }
+        if index_changed && last_index_write.elapsed() >= INDEX_WRITE_INTERVAL {
+            ix_builder.write_file(&ix.ix_path)?;
+            index_changed = false;
+            last_index_write = Instant::now();
+        }
+        if is_rotated(log_path, reader.get_ref(), offset)? {
+            // Log is a new file now, old file is read to the end already
+            ix_builder = IxBuilder::new(ix_builder.options);
+            offset = 0;
+            reader = open_at(log_path, offset)?;
+            line.clear();
+            index_changed = true;
+            continue;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn open_at(log_path: &Path, offset: u64) -> anyhow::Result<BufReader<File>> {
+    let mut file = File::open(log_path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(BufReader::new(file))
+}
+
+/// Log is rotated if it was truncated or replaced with another file
+fn is_rotated(log_path: &Path, file: &File, offset: u64) -> anyhow::Result<bool> {
+    // Log can be missing for a moment between rename and creation of new file
+    let Ok(metadata) = std::fs::metadata(log_path) else {
+        return Ok(false);
+    };
+    if metadata.len() < offset {
+        return Ok(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if metadata.ino() != file.metadata()?.ino() {
+            return Ok(true);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = file;
+    Ok(false)
+}
@@ -0,0 +1,64 @@
+use crate::time_utils::format_timestamp;
+use std::collections::BTreeMap;
+
+const MAX_BAR_WIDTH: usize = 60;
+/// Empty buckets are skipped if there are more buckets
+const MAX_BUCKETS: i64 = 1000;
+
+pub struct Histogram {
+    bucket_seconds: i64,
+    counts: BTreeMap<i64, usize>,
+    without_timestamp: usize,
+}
+
+impl Histogram {
+    pub fn new(bucket_seconds: i64) -> Self {
+        Self {
+            bucket_seconds,
+            counts: BTreeMap::new(),
+            without_timestamp: 0,
+        }
+    }
+
+    pub fn add(&mut self, timestamp: Option<i64>) {
+        match timestamp {
+            Some(timestamp) => {
+                let bucket = timestamp - timestamp.rem_euclid(self.bucket_seconds);
+                *self.counts.entry(bucket).or_default() += 1;
+            }
+            None => self.without_timestamp += 1,
+        }
+    }
+
+    pub fn print(&self) {
+        let (Some((&first, _)), Some((&last, _))) =
+            (self.counts.first_key_value(), self.counts.last_key_value())
+        else {
+            println!("No matches with timestamp");
+            return;
+        };
+        let max = self.counts.values().copied().max().unwrap_or(1);
+        let count_width = max.to_string().len();
+        let print_bucket = |bucket: i64, count: usize| {
+            let bar_width = (count * MAX_BAR_WIDTH).div_ceil(max);
+            let line = format!(
+                "{}  {count:>count_width$} {}",
+                format_timestamp(bucket),
+                "#".repeat(bar_width)
+            );
+            println!("{}", line.trim_end());
+        };
+        if (last - first) / self.bucket_seconds > MAX_BUCKETS {
+            for (&bucket, &count) in &self.counts {
+                print_bucket(bucket, count);
+            }
+        } else {
+            for bucket in (first..=last).step_by(self.bucket_seconds as usize) {
+                print_bucket(bucket, self.counts.get(&bucket).copied().unwrap_or(0));
+            }
+        }
+        if self.without_timestamp > 0 {
+            println!("Matches without timestamp: {}", self.without_timestamp);
+        }
+    }
+}
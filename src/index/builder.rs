@@ -1,50 +1,134 @@
 use crate::index::header_section::IxHeaderSection;
 use crate::index::lines_section::IxLinesSection;
 use crate::index::words_section::{IxWord, IxWordsSection};
-use std::io::{Seek, Write};
+use crate::index::{IxOptions, IxReader, LOG_HEAD_LEN, hash_bytes, read_log_head};
+use crate::query::parse_words;
+use crate::trigrams::parse_trigrams;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
 
 pub struct IxBuilder {
+    pub options: IxOptions,
     pub words_section: IxWordsSection,
     pub lines_sections: Vec<IxLinesSection>,
+    pub trigrams: Option<(IxWordsSection, Vec<IxLinesSection>)>,
+    pub lines_count: u64,
+    /// End of last line added
+    pub log_size: u64,
+    /// First bytes of indexed part of log (up to LOG_HEAD_LEN)
+    head: Vec<u8>,
 }
 
 impl IxBuilder {
-    pub fn new() -> Self {
+    pub fn new(options: IxOptions) -> Self {
         Self {
+            options,
             words_section: IxWordsSection::new(),
             lines_sections: Vec::new(),
+            trigrams: options
+                .trigrams
+                .then(|| (IxWordsSection::new(), Vec::new())),
+            lines_count: 0,
+            log_size: 0,
+            head: Vec::new(),
         }
     }
 
-    pub(crate) fn add_word(&mut self, text: String, line_offset: u64) {
-        match self
-            .words_section
-            .words
-            .binary_search_by_key(&&text, |x| &x.text)
-        {
-            Ok(index) => {
-                self.lines_sections[index].add_line_offset(line_offset);
+    /// Loads existing index to append new lines to it
+    pub fn from_reader(ix: &IxReader) -> anyhow::Result<Self> {
+        let options = IxOptions {
+            trigrams: ix.trigrams_section.is_some(),
+            positions: ix.has_positions,
+        };
+        let mut file = BufReader::new(std::fs::File::open(&ix.ix_path)?);
+        let (words_section, lines_sections) =
+            read_lines_sections(&ix.words_section, ix.has_positions, &mut file)?;
+        let trigrams = match &ix.trigrams_section {
+            Some(trigrams_section) => {
+                Some(read_lines_sections(trigrams_section, false, &mut file)?)
             }
-            Err(index) => {
-                self.words_section.words.insert(index, IxWord::new(text, 0));
-                let mut lines_section = IxLinesSection::new();
-                lines_section.add_line_offset(line_offset);
-                self.lines_sections.insert(index, lines_section);
+            None => None,
+        };
+        Ok(Self {
+            options,
+            words_section,
+            lines_sections,
+            trigrams,
+            lines_count: ix.lines_count,
+            log_size: ix.log_size,
+            head: read_log_head(&ix.log_path, ix.log_size)?,
+        })
+    }
+
+    /// Adds words (and trigrams) of complete log line, `line` includes line ending.
+    /// Unterminated last line of log must not be added, it is added when it is complete.
+    pub fn add_line(&mut self, line: &str, line_offset: u64) {
+        for (position, token) in parse_words(line).into_iter().enumerate() {
+            self.add_word(token, line_offset, position as u32);
+        }
+        if self.options.trigrams {
+            for trigram in parse_trigrams(line) {
+                self.add_trigram(trigram, line_offset);
             }
         }
+        let head_len = (LOG_HEAD_LEN - self.head.len()).min(line.len());
+        self.head.extend(&line.as_bytes()[..head_len]);
+        self.lines_count += 1;
+        self.log_size = line_offset + line.len() as u64;
+    }
+
+    pub(crate) fn add_word(&mut self, text: String, line_offset: u64, position: u32) {
+        add_posting(
+            &mut self.words_section,
+            &mut self.lines_sections,
+            text,
+            line_offset,
+            self.options.positions.then_some(position),
+        );
+    }
+
+    pub(crate) fn add_trigram(&mut self, text: String, line_offset: u64) {
+        if let Some((trigrams_section, lines_sections)) = &mut self.trigrams {
+            add_posting(trigrams_section, lines_sections, text, line_offset, None);
+        }
+    }
+
+    /// Writes index to temporary file and replaces `ix_path` with it
+    pub fn write_file(&mut self, ix_path: &Path) -> anyhow::Result<()> {
+        let tmp_path = ix_path.with_extension("ix.tmp");
+        let mut writer = BufWriter::new(std::fs::File::create(&tmp_path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(tmp_path, ix_path)?;
+        Ok(())
     }
 
     pub fn write<W: Write + Seek>(&mut self, writer: &mut W) -> anyhow::Result<()> {
         let start_position = writer.stream_position()?;
         let mut header_section = IxHeaderSection::new();
+        header_section.positions = self.options.positions;
+        header_section.lines_count = self.lines_count;
+        header_section.log_size = self.log_size;
+        header_section.head_hash = hash_bytes(&self.head);
         let mut pos = start_position + header_section.write(writer)?;
-        let words_mut = self.words_section.words.iter_mut();
-        for (word, lines_section) in words_mut.zip(self.lines_sections.iter_mut()) {
-            word.lines_section_offset = pos;
-            pos += lines_section.write(writer)?;
+        pos += write_lines_sections(
+            &mut self.words_section,
+            &self.lines_sections,
+            self.options.positions,
+            pos,
+            writer,
+        )?;
+        if let Some((trigrams_section, lines_sections)) = &mut self.trigrams {
+            pos += write_lines_sections(trigrams_section, lines_sections, false, pos, writer)?;
         }
         header_section.words_section_offset = pos;
-        self.words_section.write(writer)?;
+        self.words_section.sort_reversed();
+        pos += self.words_section.write(writer)?;
+        if let Some((trigrams_section, _)) = &self.trigrams {
+            header_section.trigrams_section_offset = pos;
+            trigrams_section.write(writer)?;
+        }
         let end_position = writer.stream_position()?;
         writer.seek(std::io::SeekFrom::Start(start_position))?;
         header_section.write(writer)?;
@@ -52,3 +136,144 @@ impl IxBuilder {
         Ok(())
     }
 }
+
+fn add_posting(
+    words_section: &mut IxWordsSection,
+    lines_sections: &mut Vec<IxLinesSection>,
+    text: String,
+    line_offset: u64,
+    position: Option<u32>,
+) {
+    let lines_section = match words_section
+        .words
+        .binary_search_by_key(&&text, |x| &x.text)
+    {
+        Ok(index) => &mut lines_sections[index],
+        Err(index) => {
+            words_section.words.insert(index, IxWord::new(text, 0));
+            lines_sections.insert(index, IxLinesSection::new());
+            &mut lines_sections[index]
+        }
+    };
+    let new_line = lines_section.add_line_offset(line_offset);
+    if let Some(position) = position {
+        lines_section.add_position(position, new_line);
+    }
+}
+
+fn write_lines_sections(
+    words_section: &mut IxWordsSection,
+    lines_sections: &[IxLinesSection],
+    with_positions: bool,
+    start: u64,
+    writer: &mut impl Write,
+) -> anyhow::Result<u64> {
+    let mut pos = start;
+    for (word, lines_section) in words_section.words.iter_mut().zip(lines_sections) {
+        word.lines_section_offset = pos;
+        word.lines_count = lines_section.lines_count() as u64;
+        pos += lines_section.write(writer, with_positions)?;
+    }
+    Ok(pos - start)
+}
+
+fn read_lines_sections<R: Read + Seek>(
+    words_section: &IxWordsSection,
+    with_positions: bool,
+    reader: &mut R,
+) -> anyhow::Result<(IxWordsSection, Vec<IxLinesSection>)> {
+    let mut loaded_words = IxWordsSection::new();
+    let mut lines_sections = Vec::with_capacity(words_section.words.len());
+    for word in &words_section.words {
+        reader.seek(SeekFrom::Start(word.lines_section_offset))?;
+        lines_sections.push(IxLinesSection::read(reader, with_positions)?);
+        loaded_words.words.push(IxWord::new(word.text.clone(), 0));
+    }
+    Ok((loaded_words, lines_sections))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{LinesReader, ix_path};
+    use std::path::PathBuf;
+
+    const OPTIONS: IxOptions = IxOptions {
+        trigrams: true,
+        positions: true,
+    };
+
+    /// Offsets of lines with positions of word within them
+    type Lines = Vec<(u64, Vec<u32>)>;
+
+    fn add_lines(builder: &mut IxBuilder, lines: &str) {
+        let mut offset = builder.log_size;
+        for line in lines.split_inclusive('\n') {
+            builder.add_line(line, offset);
+            offset += line.len() as u64;
+        }
+    }
+
+    /// Words and trigrams of index with offsets and positions of their lines
+    fn postings(log_path: PathBuf) -> Vec<(String, Lines)> {
+        let ix = IxReader::new(log_path).unwrap();
+        let trigrams = ix.trigrams_section.as_ref().unwrap();
+        let words = (ix.words_section.words.iter().map(|x| (x, true)))
+            .chain(trigrams.words.iter().map(|x| (x, false)));
+        let mut postings = Vec::new();
+        for (word, with_positions) in words {
+            let mut reader = LinesReader::with_word(&ix, word, with_positions, false).unwrap();
+            let mut lines = Vec::new();
+            while let Some(offset) = reader.next().unwrap() {
+                lines.push((offset, reader.positions().to_vec()));
+            }
+            assert_eq!(word.lines_count, lines.len() as u64);
+            postings.push((word.text.clone(), lines));
+        }
+        postings
+    }
+
+    fn header(log_path: PathBuf) -> (u64, u64, u64) {
+        let mut file = std::fs::File::open(ix_path(log_path).unwrap()).unwrap();
+        let header = IxHeaderSection::read(&mut file).unwrap();
+        (header.lines_count, header.log_size, header.head_hash)
+    }
+
+    #[test]
+    fn extended_index_equals_rebuilt_index() {
+        let old_lines = "connection timeout timeout\nretry connection\n";
+        let new_lines = "timeout again\nnew words appended\nconnection closed\n";
+        let dir = std::env::temp_dir().join(format!("qx-extend-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let extended_path = dir.join("extended.log");
+        std::fs::write(&extended_path, old_lines).unwrap();
+        let mut builder = IxBuilder::new(OPTIONS);
+        add_lines(&mut builder, old_lines);
+        builder
+            .write_file(&ix_path(extended_path.clone()).unwrap())
+            .unwrap();
+        std::fs::write(&extended_path, format!("{old_lines}{new_lines}")).unwrap();
+        let ix = IxReader::new(extended_path.clone()).unwrap();
+        let mut builder = IxBuilder::from_reader(&ix).unwrap();
+        add_lines(&mut builder, new_lines);
+        builder
+            .write_file(&ix_path(extended_path.clone()).unwrap())
+            .unwrap();
+
+        let rebuilt_path = dir.join("rebuilt.log");
+        std::fs::write(&rebuilt_path, format!("{old_lines}{new_lines}")).unwrap();
+        let mut builder = IxBuilder::new(OPTIONS);
+        add_lines(&mut builder, &format!("{old_lines}{new_lines}"));
+        builder
+            .write_file(&ix_path(rebuilt_path.clone()).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            postings(extended_path.clone()),
+            postings(rebuilt_path.clone())
+        );
+        assert_eq!(header(extended_path), header(rebuilt_path));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
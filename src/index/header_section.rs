@@ -1,31 +1,79 @@
 use crate::index::{ReadEx, WriteEx};
 use std::io::{Read, Write};
 
+pub const IX_VERSION: u16 = 6;
+
 pub struct IxHeaderSection {
     pub version: u16,
     pub words_section_offset: u64,
+    pub trigrams_section_offset: u64,
+    pub positions: bool,
+    pub lines_count: u64,
+    pub log_size: u64,
+    /// Hash of first bytes of log, tells if log was replaced
+    pub head_hash: u64,
 }
 
 impl IxHeaderSection {
     pub fn new() -> Self {
         Self {
-            version: 0,
+            version: IX_VERSION,
             words_section_offset: 0,
+            trigrams_section_offset: 0,
+            positions: false,
+            lines_count: 0,
+            log_size: 0,
+            head_hash: 0,
         }
     }
 
     pub fn read(reader: &mut impl Read) -> anyhow::Result<Self> {
         let version = reader.read_u16_be()?;
         let words_section_offset = reader.read_u64_be()?;
+        let trigrams_section_offset = if version >= 1 {
+            reader.read_u64_be()?
+        } else {
+            0
+        };
+        let positions = version >= 2 && reader.read_u8()? != 0;
+        let lines_count = if version >= 5 {
+            reader.read_u64_be()?
+        } else {
+            0
+        };
+        let log_size = if version >= 6 {
+            reader.read_u64_be()?
+        } else {
+            0
+        };
+        let head_hash = if version >= 6 {
+            reader.read_u64_be()?
+        } else {
+            0
+        };
         Ok(Self {
             version,
             words_section_offset,
+            trigrams_section_offset,
+            positions,
+            lines_count,
+            log_size,
+            head_hash,
         })
     }
 
     pub fn write(&self, writer: &mut impl Write) -> anyhow::Result<u64> {
         writer.write_u16_be(self.version)?;
         writer.write_u64_be(self.words_section_offset)?;
-        Ok(2 + 8)
+        writer.write_u64_be(self.trigrams_section_offset)?;
+        writer.write_u8(self.positions as u8)?;
+        writer.write_u64_be(self.lines_count)?;
+        writer.write_u64_be(self.log_size)?;
+        writer.write_u64_be(self.head_hash)?;
+        Ok(2 + 8 + 8 + 1 + 8 + 8 + 8)
+    }
+
+    pub fn has_trigrams(&self) -> bool {
+        self.trigrams_section_offset != 0
     }
 }
@@ -1,15 +1,39 @@
 use crate::index::lines_reader::LinesReader;
 
+/// Constraint on word positions within line, evaluated when index has positions
+#[derive(Clone, Copy)]
+pub enum PositionsConstraint {
+    /// Readers match adjacent words in readers order
+    Phrase,
+    /// Readers match words in readers order
+    Order,
+    /// Two readers match words within distance
+    Near(usize),
+}
+
 pub struct AllLinesReader {
     readers: Vec<LinesReader>,
     current: Vec<u64>,
+    constraint: Option<PositionsConstraint>,
+    reverse: bool,
+    positions: Vec<u32>,
 }
 
 impl AllLinesReader {
-    pub fn new(mut readers: Vec<LinesReader>) -> anyhow::Result<Self> {
+    pub fn new(
+        mut readers: Vec<LinesReader>,
+        constraint: Option<PositionsConstraint>,
+        reverse: bool,
+    ) -> anyhow::Result<Self> {
         let mut current = vec![0; readers.len()];
         Self::read_all_next(&mut current, &mut readers)?;
-        Ok(Self { readers, current })
+        Ok(Self {
+            readers,
+            current,
+            constraint,
+            reverse,
+            positions: Vec::new(),
+        })
     }
 
     fn read_all_next(current: &mut Vec<u64>, readers: &mut [LinesReader]) -> anyhow::Result<()> {
@@ -23,11 +47,12 @@ impl AllLinesReader {
         }
         Ok(())
     }
+
     pub fn next(&mut self) -> anyhow::Result<Option<u64>> {
-        if self.current.is_empty() {
-            return Ok(None);
-        }
         loop {
+            if self.current.is_empty() {
+                return Ok(None);
+            }
             let mut max = self.current[0];
             let mut min = max;
 
@@ -37,12 +62,17 @@ impl AllLinesReader {
             }
 
             if min == max {
+                let matched = self.match_positions();
                 Self::read_all_next(&mut self.current, &mut self.readers)?;
-                return Ok(Some(min));
+                if matched {
+                    return Ok(Some(min));
+                }
+                continue;
             }
-            // Advance readers with smallest offset
+            // Advance readers behind the farthest one
+            let target = if self.reverse { min } else { max };
             for (i, current) in self.current.iter_mut().enumerate() {
-                if *current < max {
+                if *current != target {
                     if let Some(offset) = self.readers[i].next()? {
                         *current = offset;
                     } else {
@@ -54,8 +84,81 @@ impl AllLinesReader {
         }
     }
 
+    /// Positions of matched words within last returned line
+    pub fn positions(&self) -> &[u32] {
+        &self.positions
+    }
+
+    /// Checks constraint for the line all readers stay on and collects matched positions.
+    /// Constraint is not checked if index has no positions.
+    fn match_positions(&mut self) -> bool {
+        self.positions.clear();
+        let has_positions = self.readers.iter().all(|x| !x.positions().is_empty());
+        let Some(constraint) = self.constraint.filter(|_| has_positions) else {
+            for reader in &self.readers {
+                self.positions.extend(reader.positions());
+            }
+            self.positions.sort_unstable();
+            self.positions.dedup();
+            return true;
+        };
+        match constraint {
+            PositionsConstraint::Phrase => {
+                let rest = &self.readers[1..];
+                for &start in self.readers[0].positions() {
+                    let adjacent = rest
+                        .iter()
+                        .enumerate()
+                        .all(|(i, reader)| reader.positions().contains(&(start + i as u32 + 1)));
+                    if adjacent {
+                        self.positions.push(start);
+                    }
+                }
+            }
+            PositionsConstraint::Order => {
+                let mut prev = None;
+                for reader in &self.readers {
+                    let next = reader
+                        .positions()
+                        .iter()
+                        .find(|&&position| prev.is_none_or(|prev| position > prev));
+                    match next {
+                        Some(&position) => prev = Some(position),
+                        None => return false,
+                    }
+                }
+                self.positions.extend(self.readers[0].positions().first());
+            }
+            PositionsConstraint::Near(distance) => {
+                let second = self.readers[1].positions();
+                for &first in self.readers[0].positions() {
+                    if second
+                        .iter()
+                        .any(|&x| first.abs_diff(x) as usize <= distance)
+                    {
+                        self.positions.push(first);
+                    }
+                }
+            }
+        }
+        !self.positions.is_empty()
+    }
+
+    pub fn lines_count(&self) -> usize {
+        (self.readers.iter())
+            .map(LinesReader::lines_count)
+            .min()
+            .unwrap_or_default()
+    }
+
     pub fn print_debug(&self, indent: usize) {
-        println!("{}All:", "  ".repeat(indent));
+        let name = match self.constraint {
+            None => "All".to_string(),
+            Some(PositionsConstraint::Phrase) => "Phrase".to_string(),
+            Some(PositionsConstraint::Order) => "Ordered".to_string(),
+            Some(PositionsConstraint::Near(distance)) => format!("Near/{distance}"),
+        };
+        println!("{}{name}:", "  ".repeat(indent));
         for reader in &self.readers {
             reader.print_debug(indent + 1);
         }
@@ -4,31 +4,60 @@ use std::collections::BinaryHeap;
 
 pub struct AnyLinesReader {
     pub(crate) readers: Vec<LinesReader>,
-    heap: BinaryHeap<Reverse<(u64, usize)>>, // (offset, reader_index)
+    heap: BinaryHeap<Reverse<(u64, usize)>>, // (offset key, reader_index)
+    reverse: bool,
+    positions: Vec<u32>,
 }
 
 impl AnyLinesReader {
-    pub fn new(mut readers: Vec<LinesReader>) -> anyhow::Result<Self> {
+    pub fn new(mut readers: Vec<LinesReader>, reverse: bool) -> anyhow::Result<Self> {
         let mut heap = BinaryHeap::new();
 
         for (i, reader) in readers.iter_mut().enumerate() {
             if let Some(offset) = reader.next()? {
-                heap.push(Reverse((offset, i)));
+                heap.push(Reverse((heap_key(offset, reverse), i)));
             }
         }
 
-        Ok(Self { readers, heap })
+        Ok(Self {
+            readers,
+            heap,
+            reverse,
+            positions: Vec::new(),
+        })
     }
 
     pub fn next(&mut self) -> anyhow::Result<Option<u64>> {
-        if let Some(Reverse((offset, idx))) = self.heap.pop() {
+        let Some(&Reverse((key, _))) = self.heap.peek() else {
+            return Ok(None);
+        };
+        self.positions.clear();
+        // Same line can be returned by several readers
+        while let Some(&Reverse((next_key, idx))) = self.heap.peek() {
+            if next_key != key {
+                break;
+            }
+            self.heap.pop();
+            self.positions.extend(self.readers[idx].positions());
             if let Some(next_offset) = self.readers[idx].next()? {
-                self.heap.push(Reverse((next_offset, idx)));
+                self.heap
+                    .push(Reverse((heap_key(next_offset, self.reverse), idx)));
             }
-            Ok(Some(offset))
-        } else {
-            Ok(None)
         }
+        self.positions.sort_unstable();
+        self.positions.dedup();
+        Ok(Some(heap_key(key, self.reverse)))
+    }
+
+    /// Positions of all words within last returned line
+    pub fn positions(&self) -> &[u32] {
+        &self.positions
+    }
+
+    pub fn lines_count(&self) -> usize {
+        (self.readers.iter())
+            .map(LinesReader::lines_count)
+            .fold(0, usize::saturating_add)
     }
 
     pub fn print_debug(&self, indent: usize) {
@@ -38,3 +67,8 @@ impl AnyLinesReader {
         }
     }
 }
+
+/// Heap pops smallest key first, in reverse order largest offset has to be popped first
+fn heap_key(offset: u64, reverse: bool) -> u64 {
+    if reverse { u64::MAX - offset } else { offset }
+}
@@ -0,0 +1,54 @@
+use crate::index::lines_reader::LinesReader;
+
+/// Returns lines of `included` reader missing in `excluded` reader
+pub struct ExceptLinesReader {
+    included: Box<LinesReader>,
+    excluded: Box<LinesReader>,
+    excluded_current: Option<u64>,
+    reverse: bool,
+}
+
+impl ExceptLinesReader {
+    pub fn new(
+        included: LinesReader,
+        mut excluded: LinesReader,
+        reverse: bool,
+    ) -> anyhow::Result<Self> {
+        let excluded_current = excluded.next()?;
+        Ok(Self {
+            included: Box::new(included),
+            excluded: Box::new(excluded),
+            excluded_current,
+            reverse,
+        })
+    }
+
+    pub fn next(&mut self) -> anyhow::Result<Option<u64>> {
+        while let Some(offset) = self.included.next()? {
+            while let Some(excluded) = self.excluded_current {
+                if excluded == offset || (excluded > offset) != self.reverse {
+                    break;
+                }
+                self.excluded_current = self.excluded.next()?;
+            }
+            if self.excluded_current != Some(offset) {
+                return Ok(Some(offset));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn positions(&self) -> &[u32] {
+        self.included.positions()
+    }
+
+    pub fn lines_count(&self) -> usize {
+        self.included.lines_count()
+    }
+
+    pub fn print_debug(&self, indent: usize) {
+        println!("{}Except:", "  ".repeat(indent));
+        self.included.print_debug(indent + 1);
+        self.excluded.print_debug(indent + 1);
+    }
+}
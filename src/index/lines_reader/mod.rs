@@ -1,11 +1,18 @@
 mod all;
 mod any;
+mod except;
+mod scan;
+mod tail;
 mod word;
 
 use crate::index::reader::IxReader;
 use crate::index::words_section::IxWord;
-pub use all::AllLinesReader;
+pub use all::{AllLinesReader, PositionsConstraint};
 pub use any::AnyLinesReader;
+pub use except::ExceptLinesReader;
+pub use scan::ScanLinesReader;
+use std::path::Path;
+pub use tail::TailLinesReader;
 pub use word::WordLinesReader;
 
 pub enum LinesReader {
@@ -13,26 +20,66 @@ pub enum LinesReader {
     Word(WordLinesReader),
     Any(AnyLinesReader),
     All(AllLinesReader),
+    Scan(ScanLinesReader),
+    Except(ExceptLinesReader),
+    Tail(TailLinesReader),
 }
 
 impl LinesReader {
-    pub fn with_word(ix: &IxReader, word: &IxWord) -> anyhow::Result<Self> {
-        Ok(Self::Word(WordLinesReader::new(ix, word)?))
+    pub fn with_word(
+        ix: &IxReader,
+        word: &IxWord,
+        with_positions: bool,
+        reverse: bool,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::Word(WordLinesReader::new(
+            ix,
+            word,
+            with_positions,
+            reverse,
+        )?))
     }
 
-    pub fn with_any(mut readers: Vec<Self>) -> anyhow::Result<Self> {
+    pub fn with_tail(offset: u64, positions: Vec<u32>) -> Self {
+        Self::Tail(TailLinesReader::new(offset, positions))
+    }
+
+    pub fn with_scan(log_path: impl AsRef<Path>, reverse: bool) -> anyhow::Result<Self> {
+        Ok(Self::Scan(ScanLinesReader::new(log_path, reverse)?))
+    }
+
+    /// All readers have to iterate in the same direction as `reverse` says
+    pub fn with_any(mut readers: Vec<Self>, reverse: bool) -> anyhow::Result<Self> {
         Ok(match readers.len() {
             0 => Self::Empty,
             1 => readers.pop().unwrap(),
-            _ => Self::Any(AnyLinesReader::new(readers)?),
+            _ => Self::Any(AnyLinesReader::new(readers, reverse)?),
         })
     }
 
-    pub fn with_all(mut readers: Vec<Self>) -> anyhow::Result<Self> {
+    pub fn with_all(mut readers: Vec<Self>, reverse: bool) -> anyhow::Result<Self> {
         Ok(match readers.len() {
             0 => Self::Empty,
             1 => readers.pop().unwrap(),
-            _ => Self::All(AllLinesReader::new(readers)?),
+            _ => Self::All(AllLinesReader::new(readers, None, reverse)?),
+        })
+    }
+
+    pub fn with_constrained_all(
+        readers: Vec<Self>,
+        constraint: PositionsConstraint,
+        reverse: bool,
+    ) -> anyhow::Result<Self> {
+        Ok(match readers.len() {
+            0 => Self::Empty,
+            _ => Self::All(AllLinesReader::new(readers, Some(constraint), reverse)?),
+        })
+    }
+
+    pub fn with_except(included: Self, excluded: Self, reverse: bool) -> anyhow::Result<Self> {
+        Ok(match (&included, &excluded) {
+            (Self::Empty, _) | (_, Self::Empty) => included,
+            _ => Self::Except(ExceptLinesReader::new(included, excluded, reverse)?),
         })
     }
 
@@ -41,15 +88,47 @@ impl LinesReader {
             Self::Word(reader) => reader.next(),
             Self::Any(reader) => reader.next(),
             Self::All(reader) => reader.next(),
+            Self::Scan(reader) => reader.next(),
+            Self::Except(reader) => reader.next(),
+            Self::Tail(reader) => reader.next(),
             Self::Empty => Ok(None),
         }
     }
 
+    /// Positions of matched words within last returned line, empty if index has no positions
+    pub fn positions(&self) -> &[u32] {
+        match self {
+            Self::Word(reader) => reader.positions(),
+            Self::Any(reader) => reader.positions(),
+            Self::All(reader) => reader.positions(),
+            Self::Except(reader) => reader.positions(),
+            Self::Tail(reader) => reader.positions(),
+            Self::Scan(_) | Self::Empty => &[],
+        }
+    }
+
+    /// Number of lines in word sections read, upper bound of number of lines returned.
+    /// Scanned log counts as `usize::MAX`, number of its lines is not known here.
+    pub fn lines_count(&self) -> usize {
+        match self {
+            Self::Word(reader) => reader.lines_count(),
+            Self::Any(reader) => reader.lines_count(),
+            Self::All(reader) => reader.lines_count(),
+            Self::Except(reader) => reader.lines_count(),
+            Self::Tail(_) => 1,
+            Self::Scan(_) => usize::MAX,
+            Self::Empty => 0,
+        }
+    }
+
     pub fn print_debug(&self, indent: usize) {
         match self {
             LinesReader::Word(reader) => reader.print_debug(indent),
             LinesReader::Any(reader) => reader.print_debug(indent),
             LinesReader::All(reader) => reader.print_debug(indent),
+            LinesReader::Scan(reader) => reader.print_debug(indent),
+            LinesReader::Except(reader) => reader.print_debug(indent),
+            LinesReader::Tail(reader) => reader.print_debug(indent),
             LinesReader::Empty => println!("{}Empty", "  ".repeat(indent)),
         }
     }
@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const REVERSE_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Yields offsets of all log lines, used when index can't narrow candidates.
+pub struct ScanLinesReader {
+    reader: BufReader<File>,
+    offset: u64,
+    buf: Vec<u8>,
+    reverse: bool,
+    file_len: u64,
+    /// Line offsets found in last chunk read in reverse order, last is the next one
+    reverse_offsets: Vec<u64>,
+}
+
+impl ScanLinesReader {
+    pub fn new(log_path: impl AsRef<Path>, reverse: bool) -> anyhow::Result<Self> {
+        let file = File::open(log_path)?;
+        let file_len = file.metadata()?.len();
+        // In reverse order `offset` is start of chunk read last
+        let offset = if reverse { file_len } else { 0 };
+        Ok(Self {
+            reader: BufReader::new(file),
+            offset,
+            buf: Vec::new(),
+            reverse,
+            file_len,
+            reverse_offsets: Vec::new(),
+        })
+    }
+
+    pub fn next(&mut self) -> anyhow::Result<Option<u64>> {
+        if self.reverse {
+            return self.next_reverse();
+        }
+        self.buf.clear();
+        let len = self.reader.read_until(b'\n', &mut self.buf)? as u64;
+        if len == 0 {
+            return Ok(None);
+        }
+        let offset = self.offset;
+        self.offset += len;
+        Ok(Some(offset))
+    }
+
+    fn next_reverse(&mut self) -> anyhow::Result<Option<u64>> {
+        while self.reverse_offsets.is_empty() && self.offset > 0 {
+            let chunk_start = self.offset.saturating_sub(REVERSE_CHUNK_SIZE);
+            self.buf.resize((self.offset - chunk_start) as usize, 0);
+            self.reader.seek(SeekFrom::Start(chunk_start))?;
+            self.reader.read_exact(&mut self.buf)?;
+            for (i, &b) in self.buf.iter().enumerate() {
+                let line_start = chunk_start + i as u64 + 1;
+                if b == b'\n' && line_start < self.file_len {
+                    self.reverse_offsets.push(line_start);
+                }
+            }
+            self.offset = chunk_start;
+            if chunk_start == 0 {
+                self.reverse_offsets.insert(0, 0);
+            }
+        }
+        Ok(self.reverse_offsets.pop())
+    }
+
+    pub fn print_debug(&self, indent: usize) {
+        println!("{}Scan", "  ".repeat(indent));
+    }
+}
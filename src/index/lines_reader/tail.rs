@@ -0,0 +1,27 @@
+/// Yields unterminated last line of log, it is not in index until it is complete.
+pub struct TailLinesReader {
+    offset: Option<u64>,
+    positions: Vec<u32>,
+}
+
+impl TailLinesReader {
+    pub fn new(offset: u64, positions: Vec<u32>) -> Self {
+        Self {
+            offset: Some(offset),
+            positions,
+        }
+    }
+
+    pub fn next(&mut self) -> anyhow::Result<Option<u64>> {
+        Ok(self.offset.take())
+    }
+
+    /// Positions of matched words within the line
+    pub fn positions(&self) -> &[u32] {
+        &self.positions
+    }
+
+    pub fn print_debug(&self, indent: usize) {
+        println!("{}Tail", "  ".repeat(indent));
+    }
+}
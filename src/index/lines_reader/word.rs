@@ -6,29 +6,82 @@ use std::io::Seek;
 pub struct WordLinesReader {
     word: String,
     lines_section: IxLinesSection,
+    reverse: bool,
     buf_offset: usize,
+    positions_buf_offset: usize,
+    /// Offsets of lines positions, used only in reverse order
+    positions_offsets: Vec<usize>,
+    positions: Vec<u32>,
 }
 
 impl WordLinesReader {
-    pub fn new(ix: &IxReader, word: &IxWord) -> anyhow::Result<Self> {
+    pub fn new(
+        ix: &IxReader,
+        word: &IxWord,
+        with_positions: bool,
+        reverse: bool,
+    ) -> anyhow::Result<Self> {
         let mut file = std::fs::File::open(ix.ix_path.clone())?;
-        file.seek(std::io::SeekFrom::Start(word.lines_section_offset as u64))?;
+        file.seek(std::io::SeekFrom::Start(word.lines_section_offset))?;
+        let lines_section = IxLinesSection::read(&mut file, with_positions)?;
+        let (buf_offset, positions_offsets) = if reverse {
+            (
+                lines_section.line_offsets_buf.len(),
+                lines_section.get_positions_offsets(),
+            )
+        } else {
+            (0, Vec::new())
+        };
         Ok(Self {
             word: word.text.clone(),
-            lines_section: IxLinesSection::read(&mut file)?,
-            buf_offset: 0,
+            lines_section,
+            reverse,
+            buf_offset,
+            positions_buf_offset: 0,
+            positions_offsets,
+            positions: Vec::new(),
         })
     }
 
     pub fn next(&mut self) -> anyhow::Result<Option<u64>> {
+        if self.reverse {
+            return Ok(self.next_reverse());
+        }
         if self.buf_offset >= self.lines_section.line_offsets_buf.len() {
             return Ok(None);
         }
         let offset = self.lines_section.get_line_offset(self.buf_offset);
         self.buf_offset += 5;
+        if self.lines_section.has_positions() {
+            self.positions_buf_offset = self
+                .lines_section
+                .get_positions(self.positions_buf_offset, &mut self.positions);
+        }
         Ok(Some(offset))
     }
 
+    fn next_reverse(&mut self) -> Option<u64> {
+        if self.buf_offset == 0 {
+            return None;
+        }
+        self.buf_offset -= 5;
+        let offset = self.lines_section.get_line_offset(self.buf_offset);
+        if let Some(positions_offset) = self.positions_offsets.pop() {
+            self.lines_section
+                .get_positions(positions_offset, &mut self.positions);
+        }
+        Some(offset)
+    }
+
+    /// Word positions within last returned line
+    pub fn positions(&self) -> &[u32] {
+        &self.positions
+    }
+
+    pub fn lines_count(&self) -> usize {
+        self.lines_section.lines_count()
+    }
+
     pub(crate) fn print_debug(&self, indent: usize) {
         println!("{}{}", "  ".repeat(indent), self.word)
     }
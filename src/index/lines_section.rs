@@ -4,6 +4,9 @@ use std::io::{Read, Write};
 pub struct IxLinesSection {
     pub next_section_offset: u64,
     pub line_offsets_buf: Vec<u8>,
+    /// For each line: positions count: u32, positions: u32 (token indexes within line)
+    pub positions_buf: Vec<u8>,
+    last_positions_count_offset: usize,
 }
 
 impl IxLinesSection {
@@ -11,27 +14,50 @@ impl IxLinesSection {
         Self {
             next_section_offset: 0,
             line_offsets_buf: Vec::new(),
+            positions_buf: Vec::new(),
+            last_positions_count_offset: 0,
         }
     }
 
-    pub fn write(&self, writer: &mut impl Write) -> anyhow::Result<u64> {
+    pub fn write(&self, writer: &mut impl Write, with_positions: bool) -> anyhow::Result<u64> {
         writer.write_u64_be(self.next_section_offset)?;
-        Ok(8 + writer.write_compressed(&self.line_offsets_buf)?)
+        let mut size = 8 + writer.write_compressed(&self.line_offsets_buf)?;
+        if with_positions {
+            size += writer.write_compressed(&self.positions_buf)?;
+        }
+        Ok(size)
     }
 
-    pub fn read(reader: &mut impl Read) -> anyhow::Result<Self> {
+    pub fn read(reader: &mut impl Read, with_positions: bool) -> anyhow::Result<Self> {
         let next_section_offset = reader.read_u64_be()?;
         let line_offsets_buf = reader.read_compressed()?;
+        let positions_buf = if with_positions {
+            reader.read_compressed()?
+        } else {
+            Vec::new()
+        };
         Ok(Self {
             next_section_offset,
             line_offsets_buf,
+            positions_buf,
+            last_positions_count_offset: 0,
         })
     }
 
-    pub fn add_line_offset(&mut self, offset: u64) {
+    /// Returns false if offset is already added as last line
+    pub fn add_line_offset(&mut self, offset: u64) -> bool {
+        let len = self.line_offsets_buf.len();
+        if len >= 5 && self.get_line_offset(len - 5) == offset {
+            return false;
+        }
         let mut offset_buf = [0u8; 5];
         offset_buf.copy_from_slice(&offset.to_le_bytes()[0..5]);
         self.line_offsets_buf.extend(&offset_buf);
+        true
+    }
+
+    pub fn lines_count(&self) -> usize {
+        self.line_offsets_buf.len() / 5
     }
 
     pub fn get_line_offset(&self, buf_offset: usize) -> u64 {
@@ -39,4 +65,47 @@ impl IxLinesSection {
         buf[0..5].copy_from_slice(&self.line_offsets_buf[buf_offset..buf_offset + 5]);
         u64::from_le_bytes(buf)
     }
+
+    /// Adds position of word within last added line
+    pub fn add_position(&mut self, position: u32, new_line: bool) {
+        if new_line {
+            self.last_positions_count_offset = self.positions_buf.len();
+            self.positions_buf.extend(0u32.to_le_bytes());
+        }
+        let count_offset = self.last_positions_count_offset;
+        let count = self.get_u32(count_offset) + 1;
+        self.positions_buf[count_offset..count_offset + 4].copy_from_slice(&count.to_le_bytes());
+        self.positions_buf.extend(position.to_le_bytes());
+    }
+
+    /// Reads positions of line starting at `buf_offset`, returns offset of next line positions
+    pub fn get_positions(&self, buf_offset: usize, positions: &mut Vec<u32>) -> usize {
+        positions.clear();
+        let count = self.get_u32(buf_offset) as usize;
+        for i in 0..count {
+            positions.push(self.get_u32(buf_offset + 4 + i * 4));
+        }
+        buf_offset + 4 + count * 4
+    }
+
+    /// Offsets of positions of every line, allows reading positions in reverse order
+    pub fn get_positions_offsets(&self) -> Vec<usize> {
+        let mut offsets = Vec::with_capacity(self.lines_count());
+        let mut buf_offset = 0;
+        while buf_offset < self.positions_buf.len() {
+            offsets.push(buf_offset);
+            buf_offset += 4 + self.get_u32(buf_offset) as usize * 4;
+        }
+        offsets
+    }
+
+    pub fn has_positions(&self) -> bool {
+        !self.positions_buf.is_empty()
+    }
+
+    fn get_u32(&self, buf_offset: usize) -> u32 {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(&self.positions_buf[buf_offset..buf_offset + 4]);
+        u32::from_le_bytes(buf)
+    }
 }
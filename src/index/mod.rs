@@ -6,18 +6,45 @@ mod reader;
 mod words_section;
 
 pub use builder::IxBuilder;
+use header_section::IX_VERSION;
+pub use header_section::IxHeaderSection;
+pub use lines_reader::LinesReader;
 pub use reader::IxReader;
 use std::io::{Cursor, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Default)]
+pub struct IxOptions {
+    pub trigrams: bool,
+    pub positions: bool,
+}
+
+impl IxOptions {
+    pub fn satisfied_by(&self, header: &IxHeaderSection) -> bool {
+        header.version == IX_VERSION
+            && (!self.trigrams || header.has_trigrams())
+            && (!self.positions || header.positions)
+    }
+}
 
 // Ix file structure:
 // Header:
 //   version: u16
 //   words_section_offset: u64
+//   trigrams_section_offset: u64 (since version 1, 0 if index has no trigrams)
+//   positions: u8 (since version 2, 1 if word lines sections have positions)
+//   lines_count: u64 (since version 5, number of lines in log)
+//   log_size: u64 (since version 6, size of indexed part of log, only complete lines are counted)
+//   head_hash: u64 (since version 6, FNV-1a hash of first LOG_HEAD_LEN bytes of indexed part of log)
+// Lines sections (for each word, then for each trigram):
+//   next_section_offset: u64
+//   line_offsets: compressed
+//   positions: compressed (only for words, if index has positions)
 // Words section:
-//
-// Lines section:
-//
+//   compressed list of (text, lines_section_offset, lines_count (since version 4))
+//   followed by list of u32 word indexes sorted by reversed text (since version 3)
+// Trigrams section (optional):
+//   same as words section, list of reversed words is empty
 
 pub trait ReadEx {
     fn read_u8(&mut self) -> anyhow::Result<u8>;
@@ -29,7 +56,7 @@ pub trait ReadEx {
 impl<R: Read> ReadEx for R {
     fn read_u8(&mut self) -> anyhow::Result<u8> {
         let mut buf = [0u8; 1];
-        self.read(&mut buf)?;
+        self.read_exact(&mut buf)?;
         Ok(buf[0])
     }
 
@@ -60,28 +87,47 @@ pub trait WriteEx {
 
 impl<W: Write> WriteEx for W {
     fn write_u8(&mut self, v: u8) -> anyhow::Result<()> {
-        self.write(&[v])?;
+        self.write_all(&[v])?;
         Ok(())
     }
 
     fn write_u16_be(&mut self, v: u16) -> anyhow::Result<()> {
-        self.write(&v.to_be_bytes())?;
+        self.write_all(&v.to_be_bytes())?;
         Ok(())
     }
 
     fn write_u64_be(&mut self, v: u64) -> anyhow::Result<()> {
-        self.write(&v.to_be_bytes())?;
+        self.write_all(&v.to_be_bytes())?;
         Ok(())
     }
 
     fn write_compressed(&mut self, data: &[u8]) -> anyhow::Result<u64> {
         let compressed = zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL)?;
         self.write_u64_be(compressed.len() as u64)?;
-        self.write(&compressed)?;
+        self.write_all(&compressed)?;
         Ok(8 + compressed.len() as u64)
     }
 }
 
+/// Number of first log bytes identifying log file
+pub const LOG_HEAD_LEN: usize = 4096;
+
+/// Reads first bytes of log identifying it, at most `log_size` bytes are read
+pub fn read_log_head(log_path: &Path, log_size: u64) -> anyhow::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    std::fs::File::open(log_path)?
+        .take(log_size.min(LOG_HEAD_LEN as u64))
+        .read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// FNV-1a hash, stable between builds unlike std hashers
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 pub fn ix_path(mut path: PathBuf) -> anyhow::Result<PathBuf> {
     path.set_file_name(format!(
         "{}.ix",
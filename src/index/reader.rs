@@ -1,16 +1,54 @@
-use crate::file_utils::LogLine;
+use crate::file_utils::{LogLine, LogReader};
+use crate::filter::LineFilter;
 use crate::index::header_section::IxHeaderSection;
 use crate::index::ix_path;
-use crate::index::lines_reader::LinesReader;
-use crate::index::words_section::IxWordsSection;
-use crate::query::Query;
-use std::io::{Seek, SeekFrom};
-use std::path::PathBuf;
+use crate::index::lines_reader::{LinesReader, PositionsConstraint};
+use crate::index::words_section::{IxWord, IxWordsSection};
+use crate::query::{Query, QueryOptions, parse_words};
+use crate::trigrams::parse_trigrams;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 
 pub struct IxReader {
-    log_path: PathBuf,
+    pub log_path: PathBuf,
     pub ix_path: PathBuf,
     pub words_section: IxWordsSection,
+    pub trigrams_section: Option<IxWordsSection>,
+    pub has_positions: bool,
+    pub lines_count: u64,
+    pub log_size: u64,
+    /// Log file kept open for reading lines, shared by all reads
+    log_reader: RefCell<LogReader>,
+    /// Unterminated last line of log, it is searched without index
+    tail: Option<LogTail>,
+}
+
+struct LogTail {
+    offset: u64,
+    line: String,
+    words: Vec<String>,
+}
+
+impl LogTail {
+    /// Reads last line of log after indexed part if it is unterminated
+    fn read(log_path: &Path, offset: u64) -> anyhow::Result<Option<Self>> {
+        let mut file = std::fs::File::open(log_path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        // Complete lines appended after indexing are not searched until reindex
+        if buf.is_empty() || buf.contains(&b'\n') {
+            return Ok(None);
+        }
+        let line = String::from_utf8_lossy(&buf).into_owned();
+        Ok(Some(Self {
+            offset,
+            words: parse_words(&line),
+            line,
+        }))
+    }
 }
 
 impl IxReader {
@@ -18,12 +56,24 @@ impl IxReader {
         let ix_path = ix_path(log_path.clone())?;
         let mut file = std::fs::File::open(ix_path.clone())?;
         let header = IxHeaderSection::read(&mut file)?;
-        file.seek(SeekFrom::Start(header.words_section_offset as u64))?;
+        file.seek(SeekFrom::Start(header.words_section_offset))?;
         let words_section = IxWordsSection::read(&mut file)?;
+        let trigrams_section = if header.has_trigrams() {
+            file.seek(SeekFrom::Start(header.trigrams_section_offset))?;
+            Some(IxWordsSection::read(&mut file)?)
+        } else {
+            None
+        };
         Ok(Self {
+            log_reader: RefCell::new(LogReader::new(&log_path)?),
+            tail: LogTail::read(&log_path, header.log_size)?,
             log_path,
             ix_path,
             words_section,
+            trigrams_section,
+            has_positions: header.positions,
+            lines_count: header.lines_count,
+            log_size: header.log_size,
         })
     }
 
@@ -32,6 +82,21 @@ impl IxReader {
         for word in &self.words_section.words {
             println!("  {}", word.text);
         }
+        if let Some(trigrams_section) = &self.trigrams_section {
+            println!("Trigrams: {}", trigrams_section.words.len());
+        }
+        if self.has_positions {
+            println!("Positions: yes");
+        }
+    }
+
+    /// Returns words starting with prefix and number of lines containing them
+    pub fn lines_counts(&self, prefix: &str) -> Vec<(String, usize)> {
+        self.words_section
+            .select_words(prefix, false)
+            .into_iter()
+            .map(|x| (x.text.clone(), x.lines_count as usize))
+            .collect()
     }
 
     pub fn read_log(
@@ -39,32 +104,311 @@ impl IxReader {
         line_offset: u64,
         before: usize,
         after: usize,
-        check_order: Option<&Query>,
+        filters: &[LineFilter],
     ) -> anyhow::Result<Option<LogLine>> {
-        LogLine::read(&self.log_path, line_offset, before, after, check_order)
+        self.log_reader
+            .borrow_mut()
+            .read(line_offset, before, after, filters)
     }
 
-    pub fn query(&self, query: &Query, whole_words: bool) -> anyhow::Result<LinesReader> {
+    /// Phrases, proximity and words order are evaluated here only if index has positions,
+    /// otherwise they have to be verified by line filters.
+    pub fn query(&self, query: &Query, options: QueryOptions) -> anyhow::Result<LinesReader> {
         match query {
-            Query::Word(word) => LinesReader::with_any(
+            Query::Word(word) => self.query_word(word, options),
+            Query::Fuzzy(word, distance) => self.query_word(word, options.with_fuzzy(*distance)),
+            Query::Wildcard(pattern) => LinesReader::with_any(
                 self.words_section
-                    .select_words(&word, whole_words)
+                    .select_wildcard(pattern)
                     .into_iter()
-                    .map(|x| LinesReader::with_word(self, x))
+                    .map(|x| LinesReader::with_word(self, x, self.has_positions, options.reverse))
+                    .chain(self.query_tail(query, options).map(Ok))
                     .collect::<Result<_, _>>()?,
+                options.reverse,
             ),
             Query::Any(queries) => LinesReader::with_any(
                 queries
+                    .iter()
+                    .map(|x| self.query(x, options))
+                    .collect::<Result<_, _>>()?,
+                options.reverse,
+            ),
+            Query::All(queries) => {
+                let (excluded, included): (Vec<_>, Vec<_>) =
+                    queries.iter().partition(|x| matches!(x, Query::Not(_)));
+                let readers = included
+                    .into_iter()
+                    .map(|x| self.query(x, options))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let included = if readers.is_empty() {
+                    LinesReader::with_scan(&self.log_path, options.reverse)?
+                } else if options.order_important {
+                    LinesReader::with_constrained_all(
+                        readers,
+                        PositionsConstraint::Order,
+                        options.reverse,
+                    )?
+                } else {
+                    LinesReader::with_all(readers, options.reverse)?
+                };
+                self.query_except(included, excluded, options)
+            }
+            Query::Not(_) => self.query_except(
+                LinesReader::with_scan(&self.log_path, options.reverse)?,
+                vec![query],
+                options,
+            ),
+            Query::Phrase(words) => LinesReader::with_constrained_all(
+                words
+                    .iter()
+                    .map(|x| self.query_word(x, options))
+                    .collect::<Result<_, _>>()?,
+                PositionsConstraint::Phrase,
+                options.reverse,
+            ),
+            Query::Near(first, second, distance) => LinesReader::with_constrained_all(
+                vec![
+                    self.query_word(first, options)?,
+                    self.query_word(second, options)?,
+                ],
+                PositionsConstraint::Near(*distance),
+                options.reverse,
+            ),
+        }
+    }
+
+    fn query_except(
+        &self,
+        included: LinesReader,
+        excluded: Vec<&Query>,
+        options: QueryOptions,
+    ) -> anyhow::Result<LinesReader> {
+        let mut readers = Vec::new();
+        for query in excluded {
+            let Query::Not(query) = query else {
+                continue;
+            };
+            // Without positions index can't exclude by phrase, line filter does it
+            if self.has_positions || !query.has_proximity() {
+                readers.push(self.query(query, options)?);
+            }
+        }
+        LinesReader::with_except(
+            included,
+            LinesReader::with_any(readers, options.reverse)?,
+            options.reverse,
+        )
+    }
+
+    /// Dictionary words query word expands to
+    fn select_words(&self, word: &str, options: QueryOptions) -> Vec<&IxWord> {
+        let mut words = self.words_section.select_words(word, options.whole_words);
+        if options.fuzzy > 0 {
+            for fuzzy_word in self.words_section.select_fuzzy(word, options.fuzzy) {
+                if !words.iter().any(|x| std::ptr::eq(*x, fuzzy_word)) {
+                    words.push(fuzzy_word);
+                }
+            }
+        }
+        words
+    }
+
+    /// Dictionary words and words of unterminated last line matched by query terms,
+    /// excluded terms are skipped
+    pub fn expand_terms(&self, query: &Query, options: QueryOptions) -> HashSet<String> {
+        let mut terms = HashSet::new();
+        self.inner_expand_terms(query, options, &mut terms);
+        if let Some(tail) = &self.tail {
+            terms.extend(
+                (tail.words.iter())
+                    .filter(|x| query.matches_token(x, options))
+                    .cloned(),
+            );
+        }
+        terms
+    }
+
+    fn inner_expand_terms(
+        &self,
+        query: &Query,
+        options: QueryOptions,
+        terms: &mut HashSet<String>,
+    ) {
+        let mut add = |words: Vec<&IxWord>| terms.extend(words.into_iter().map(|x| x.text.clone()));
+        match query {
+            Query::Word(word) => add(self.select_words(word, options)),
+            Query::Fuzzy(word, distance) => {
+                add(self.select_words(word, options.with_fuzzy(*distance)))
+            }
+            Query::Wildcard(pattern) => add(self.words_section.select_wildcard(pattern)),
+            Query::Phrase(words) => {
+                for word in words {
+                    add(self.select_words(word, options));
+                }
+            }
+            Query::Near(first, second, _) => {
+                add(self.select_words(first, options));
+                add(self.select_words(second, options));
+            }
+            Query::Not(_) => {}
+            Query::Any(queries) | Query::All(queries) => {
+                for query in queries {
+                    self.inner_expand_terms(query, options, terms);
+                }
+            }
+        }
+    }
+
+    fn query_word(&self, word: &str, options: QueryOptions) -> anyhow::Result<LinesReader> {
+        LinesReader::with_any(
+            self.select_words(word, options)
+                .into_iter()
+                .map(|x| LinesReader::with_word(self, x, self.has_positions, options.reverse))
+                .chain(
+                    self.query_tail(&Query::Word(word.to_string()), options)
+                        .map(Ok),
+                )
+                .collect::<Result<_, _>>()?,
+            options.reverse,
+        )
+    }
+
+    /// Reader of unterminated last line if it contains word matched by single term `query`
+    fn query_tail(&self, query: &Query, options: QueryOptions) -> Option<LinesReader> {
+        let tail = self.tail.as_ref()?;
+        let positions: Vec<u32> = (tail.words.iter().enumerate())
+            .filter(|(_, word)| query.matches_token(word, options))
+            .map(|(position, _)| position as u32)
+            .collect();
+        if positions.is_empty() {
+            return None;
+        }
+        let positions = if self.has_positions {
+            positions
+        } else {
+            Vec::new()
+        };
+        Some(LinesReader::with_tail(tail.offset, positions))
+    }
+
+    /// Selects candidate lines by trigrams query, scans all lines if there is nothing to narrow.
+    pub fn query_trigrams(
+        &self,
+        query: Option<&Query>,
+        reverse: bool,
+    ) -> anyhow::Result<LinesReader> {
+        let (Some(query), Some(trigrams_section)) = (query, &self.trigrams_section) else {
+            return LinesReader::with_scan(&self.log_path, reverse);
+        };
+        self.query_trigrams_section(query, trigrams_section, reverse)
+    }
+
+    fn query_trigrams_section(
+        &self,
+        query: &Query,
+        trigrams_section: &IxWordsSection,
+        reverse: bool,
+    ) -> anyhow::Result<LinesReader> {
+        match query {
+            Query::Word(trigram) => LinesReader::with_any(
+                trigrams_section
+                    .select_words(trigram, true)
                     .into_iter()
-                    .map(|x| self.query(x, whole_words))
+                    .map(|x| LinesReader::with_word(self, x, false, reverse))
+                    .chain(
+                        (self.tail.as_ref())
+                            .filter(|x| parse_trigrams(&x.line).contains(trigram))
+                            .map(|x| Ok(LinesReader::with_tail(x.offset, Vec::new()))),
+                    )
                     .collect::<Result<_, _>>()?,
+                reverse,
+            ),
+            Query::Any(queries) => LinesReader::with_any(
+                queries
+                    .iter()
+                    .map(|x| self.query_trigrams_section(x, trigrams_section, reverse))
+                    .collect::<Result<_, _>>()?,
+                reverse,
             ),
             Query::All(queries) => LinesReader::with_all(
                 queries
-                    .into_iter()
-                    .map(|x| self.query(x, whole_words))
+                    .iter()
+                    .map(|x| self.query_trigrams_section(x, trigrams_section, reverse))
                     .collect::<Result<_, _>>()?,
+                reverse,
             ),
+            _ => anyhow::bail!("Trigrams query can contain only words"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{IxBuilder, IxOptions};
+
+    /// Indexes complete lines of `log` written to temporary dir like `qx` does
+    fn build_index(name: &str, log: &str) -> IxReader {
+        let dir = std::env::temp_dir().join(format!("qx-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("test.log");
+        std::fs::write(&log_path, log).unwrap();
+        let mut builder = IxBuilder::new(IxOptions {
+            trigrams: false,
+            positions: true,
+        });
+        let mut offset = 0;
+        for line in log.split_inclusive('\n').filter(|x| x.ends_with('\n')) {
+            builder.add_line(line, offset);
+            offset += line.len() as u64;
+        }
+        builder
+            .write_file(&ix_path(log_path.clone()).unwrap())
+            .unwrap();
+        IxReader::new(log_path).unwrap()
+    }
+
+    fn query_lines(ix: &IxReader, query: &str, reverse: bool) -> Vec<(u64, Vec<u32>)> {
+        let options = QueryOptions {
+            reverse,
+            ..QueryOptions::default()
+        };
+        let query = Query::parse(query).unwrap().unwrap();
+        let mut lines = ix.query(&query, options).unwrap();
+        let mut result = Vec::new();
+        while let Some(offset) = lines.next().unwrap() {
+            result.push((offset, lines.positions().to_vec()));
+        }
+        result
+    }
+
+    #[test]
+    fn reverse_order_is_reversed_forward_order() {
+        // Log bigger than chunk of reverse scan, with unterminated last line
+        let mut log = String::new();
+        for i in 0..3000 {
+            let level = ["error", "warn", "info"][i % 3];
+            let device = ["disk", "net", "cpu", "mem"][i % 4];
+            log += &format!("{level} {device} {}\n", "padding".repeat(i % 7));
+        }
+        log += "error disk tail";
+        let ix = build_index("reverse", &log);
+        for query in [
+            "error",
+            "err*",
+            "error | net",
+            "error disk",
+            "\"warn cpu\"",
+            "error -disk",
+            "-disk",
+            "-(error | warn)",
+        ] {
+            let forward = query_lines(&ix, query, false);
+            let mut reverse = query_lines(&ix, query, true);
+            reverse.reverse();
+            assert!(!forward.is_empty(), "{query}");
+            assert_eq!(forward, reverse, "{query}");
         }
+        std::fs::remove_dir_all(ix.log_path.parent().unwrap()).unwrap();
     }
 }
@@ -1,9 +1,12 @@
 use crate::index::{ReadEx, WriteEx};
+use crate::query::wildcard_matches;
 use std::io::{Cursor, Read, Write};
 
 pub struct IxWord {
     pub text: String,
     pub lines_section_offset: u64,
+    /// Number of lines containing word, set when index is written
+    pub lines_count: u64,
 }
 
 impl IxWord {
@@ -11,17 +14,23 @@ impl IxWord {
         Self {
             text,
             lines_section_offset,
+            lines_count: 0,
         }
     }
 }
 
 pub struct IxWordsSection {
     pub words: Vec<IxWord>,
+    /// Indexes in `words` sorted by reversed text for suffix queries, empty for trigrams
+    pub reversed_words: Vec<u32>,
 }
 
 impl IxWordsSection {
     pub fn new() -> IxWordsSection {
-        Self { words: Vec::new() }
+        Self {
+            words: Vec::new(),
+            reversed_words: Vec::new(),
+        }
     }
 
     pub fn write(&self, writer: &mut impl Write) -> anyhow::Result<u64> {
@@ -31,10 +40,15 @@ impl IxWordsSection {
         for word in &self.words {
             let text_bytes = word.text.as_bytes();
             buf_writer.write_u8(text_bytes.len() as u8)?;
-            buf_writer.write(text_bytes)?;
+            buf_writer.write_all(text_bytes)?;
             buf_writer.write_u64_be(word.lines_section_offset)?;
+            buf_writer.write_u64_be(word.lines_count)?;
+        }
+        buf_writer.write_u64_be(self.reversed_words.len() as u64)?;
+        for i in &self.reversed_words {
+            buf_writer.write_all(&i.to_be_bytes())?;
         }
-        Ok(writer.write_compressed(&buf)?)
+        writer.write_compressed(&buf)
     }
 
     pub fn read(reader: &mut impl Read) -> anyhow::Result<Self> {
@@ -48,12 +62,34 @@ impl IxWordsSection {
             words_reader.read_exact(&mut text_bytes)?;
             let text = String::from_utf8(text_bytes)?;
             let lines_section_offset = words_reader.read_u64_be()?;
+            let lines_count = words_reader.read_u64_be()?;
             words.push(IxWord {
                 text,
                 lines_section_offset,
+                lines_count,
             })
         }
-        Ok(Self { words })
+        let reversed_len = words_reader.read_u64_be()? as usize;
+        let mut reversed_words = Vec::with_capacity(reversed_len);
+        for _ in 0..reversed_len {
+            let mut index_bytes = [0u8; 4];
+            words_reader.read_exact(&mut index_bytes)?;
+            reversed_words.push(u32::from_be_bytes(index_bytes));
+        }
+        Ok(Self {
+            words,
+            reversed_words,
+        })
+    }
+
+    /// Sorts word indexes by reversed text, called before writing words of index
+    pub fn sort_reversed(&mut self) {
+        let words = &self.words;
+        self.reversed_words = (0..words.len() as u32).collect();
+        self.reversed_words.sort_by(|a, b| {
+            let a = words[*a as usize].text.chars().rev();
+            a.cmp(words[*b as usize].text.chars().rev())
+        });
     }
 
     pub fn select_words(&self, prefix: &str, whole_words: bool) -> Vec<&IxWord> {
@@ -75,14 +111,94 @@ impl IxWordsSection {
             }
         };
         let mut i = pos;
-        while i > 0 && self.words[i - 1].text.starts_with(&prefix) {
+        while i > 0 && self.words[i - 1].text.starts_with(prefix) {
             tokens.push(&self.words[i - 1]);
             i -= 1;
         }
-        while pos < self.words.len() && self.words[pos].text.starts_with(&prefix) {
+        while pos < self.words.len() && self.words[pos].text.starts_with(prefix) {
             tokens.push(&self.words[pos]);
             pos += 1;
         }
         tokens
     }
+
+    /// Selects words within edit distance (Levenshtein with transpositions, so `RECIEVE` is
+    /// within 1 from `RECEIVE`). Walks sorted words like a trie: distance rows
+    /// are reused for common prefix of adjacent words, and all words with a prefix that
+    /// can't match anymore are skipped.
+    pub fn select_fuzzy(&self, word: &str, distance: usize) -> Vec<&IxWord> {
+        let target: Vec<char> = word.chars().collect();
+        let mut rows: Vec<Vec<usize>> = vec![(0..=target.len()).collect()];
+        let mut prev_chars: Vec<char> = Vec::new();
+        let mut selected = Vec::new();
+        let mut i = 0;
+        while i < self.words.len() {
+            let chars: Vec<char> = self.words[i].text.chars().collect();
+            let common = chars
+                .iter()
+                .zip(&prev_chars)
+                .take_while(|(a, b)| a == b)
+                .count()
+                .min(rows.len() - 1);
+            rows.truncate(common + 1);
+            let mut dead_prefix_len = None;
+            for (k, c) in chars.iter().enumerate().skip(common) {
+                let prev_row = &rows[k];
+                let mut row = vec![k + 1; target.len() + 1];
+                for j in 0..target.len() {
+                    let substitution = prev_row[j] + usize::from(target[j] != *c);
+                    row[j + 1] = substitution.min(prev_row[j + 1] + 1).min(row[j] + 1);
+                    if k > 0 && j > 0 && target[j] == chars[k - 1] && target[j - 1] == *c {
+                        row[j + 1] = row[j + 1].min(rows[k - 1][j - 1] + 1);
+                    }
+                }
+                let min = *row.iter().min().unwrap_or(&0);
+                rows.push(row);
+                if min > distance {
+                    dead_prefix_len = Some(k + 1);
+                    break;
+                }
+            }
+            if let Some(len) = dead_prefix_len {
+                let prefix: String = chars[..len].iter().collect();
+                i += self.words[i..].partition_point(|x| x.text.starts_with(&prefix));
+            } else {
+                if rows[chars.len()][target.len()] <= distance {
+                    selected.push(&self.words[i]);
+                }
+                i += 1;
+            }
+            prev_chars = chars;
+        }
+        selected
+    }
+
+    /// Selects words matching pattern with `*` wildcards. Candidates are narrowed by pattern
+    /// prefix in words or by pattern suffix in reversed words, otherwise all words are scanned.
+    pub fn select_wildcard(&self, pattern: &str) -> Vec<&IxWord> {
+        let (prefix, _) = pattern.split_once('*').unwrap_or((pattern, ""));
+        let (_, suffix) = pattern.rsplit_once('*').unwrap_or(("", pattern));
+        let mut selected: Vec<&IxWord> = if !prefix.is_empty() {
+            self.select_words(prefix, false)
+        } else if !suffix.is_empty() {
+            self.select_suffix(suffix)
+        } else {
+            self.words.iter().collect()
+        };
+        selected.retain(|x| wildcard_matches(&x.text, pattern));
+        selected.sort_by(|a, b| a.text.cmp(&b.text));
+        selected
+    }
+
+    fn select_suffix(&self, suffix: &str) -> Vec<&IxWord> {
+        let word = |i: &u32| &self.words[*i as usize];
+        let start = self
+            .reversed_words
+            .partition_point(|i| word(i).text.chars().rev().lt(suffix.chars().rev()));
+        self.reversed_words[start..]
+            .iter()
+            .map(word)
+            .take_while(|x| x.text.ends_with(suffix))
+            .collect()
+    }
 }
@@ -1,18 +1,44 @@
 mod file_utils;
+mod filter;
+mod follow;
+mod histogram;
 mod index;
+mod merge;
+mod pager;
 mod path_utils;
 mod print_utils;
 mod query;
+mod rank;
+mod terms;
+mod time_utils;
+mod trigrams;
+mod tui;
 
-use crate::index::{IxBuilder, IxReader, ix_path};
+use crate::file_utils::LogLine;
+use crate::filter::LineFilter;
+use crate::follow::follow_log;
+use crate::histogram::Histogram;
+use crate::index::{
+    IxBuilder, IxHeaderSection, IxOptions, IxReader, LinesReader, hash_bytes, ix_path,
+    read_log_head,
+};
+use crate::merge::{LinesMerger, MergeSource};
+use crate::pager::start_pager;
 use crate::path_utils::resolve_log_files;
-use crate::print_utils::print_line;
-use crate::query::{Query, parse_words};
-use clap::Parser;
-use std::collections::VecDeque;
+use crate::print_utils::LinePrinter;
+use crate::query::{Query, QueryOptions, parse_word_spans, parse_words};
+use crate::rank::rank_lines;
+use crate::terms::{TermsArgs, run_terms};
+use crate::time_utils::{parse_duration, parse_line_timestamp, parse_timestamp};
+use crate::trigrams::{regex_query, substring_query};
+use crate::tui::{TuiArgs, run_tui};
+use clap::{Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use std::collections::{HashSet, VecDeque};
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, IsTerminal, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(
@@ -20,12 +46,21 @@ use std::path::PathBuf;
     about = "Log indexer and searcher",
     long_about = None,
     disable_help_flag = true,
-    arg_required_else_help = true
+    arg_required_else_help = true,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to log file
-    #[arg(short, long)]
-    path: PathBuf,
+    #[arg(short, long, required = true)]
+    path: Option<PathBuf>,
+
+    /// Print output directly instead of paging it when it is longer than terminal
+    #[arg(long, default_value = "false")]
+    no_pager: bool,
 
     /// Print debug info
     #[arg(long, default_value = "false")]
@@ -37,9 +72,25 @@ struct Cli {
     #[arg(short, long, default_value = "0")]
     after: usize,
 
-    #[arg(short, long, default_value = "0")]
+    #[arg(short = 'C', long, default_value = "0")]
     context: usize,
 
+    /// Print number of matching lines instead of lines
+    #[arg(short, long, default_value = "false")]
+    count: bool,
+
+    /// Print number of matching lines per time bucket (like 30s, 1m, 1h, 1d)
+    #[arg(long, value_name = "BUCKET", conflicts_with = "count")]
+    histogram: Option<String>,
+
+    /// Only lines with timestamp since this time (like 2025-03-27T12:00)
+    #[arg(long, value_name = "TIME")]
+    since: Option<String>,
+
+    /// Only lines with timestamp before this time (like 2025-03-27T13:00)
+    #[arg(long, value_name = "TIME")]
+    until: Option<String>,
+
     #[arg(short, long, default_value = "0")]
     head: usize,
 
@@ -55,132 +106,842 @@ struct Cli {
     #[arg(short, long, default_value = "false")]
     force_reindex: bool,
 
+    /// Output format of matched lines
+    #[arg(long, value_enum, default_value = "text", conflicts_with_all = ["count", "histogram"])]
+    output: OutputFormat,
+
+    /// When to color output (`auto` colors terminal output unless NO_COLOR is set)
+    #[arg(long, value_enum, default_value = "auto", value_name = "WHEN")]
+    color: ColorChoice,
+
+    /// Print file name before every line
+    #[arg(short = 'H', long, default_value = "false")]
+    with_filename: bool,
+
+    /// Print file name before every line instead of heading with file name for multiple files
+    #[arg(long, default_value = "false")]
+    no_heading: bool,
+
+    /// Print only names of files with matching lines
+    #[arg(
+        short = 'l',
+        long,
+        default_value = "false",
+        conflicts_with_all = ["count", "histogram", "rank", "merge", "follow"]
+    )]
+    files_with_matches: bool,
+
+    /// Print only names of files without matching lines
+    #[arg(
+        short = 'L',
+        long,
+        default_value = "false",
+        conflicts_with_all = ["count", "histogram", "rank", "merge", "follow", "files_with_matches"]
+    )]
+    files_without_match: bool,
+
+    /// Print lines not matching query
+    #[arg(short = 'v', long, default_value = "false", conflicts_with_all = ["rank", "follow"])]
+    invert_match: bool,
+
+    /// Print only matched parts of lines, each on its own line
+    #[arg(long, default_value = "false", conflicts_with_all = ["count", "histogram"])]
+    only_matching: bool,
+
+    /// Print newest matches first
+    #[arg(long, default_value = "false")]
+    reverse: bool,
+
+    /// Print matches of all files as one stream ordered by line timestamps
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["count", "histogram", "rank", "reverse", "follow"]
+    )]
+    merge: bool,
+
+    /// Keep printing matching lines appended to log (like `tail -f`)
+    #[arg(
+        short = 'F',
+        long,
+        default_value = "false",
+        conflicts_with_all = ["count", "histogram", "rank", "reverse"]
+    )]
+    follow: bool,
+
+    /// Match lines containing any of the words instead of all of them
+    #[arg(long, default_value = "false")]
+    any_terms: bool,
+
+    /// Print most relevant lines first (top 10 or --head lines)
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["count", "histogram", "tail", "substring", "regex"]
+    )]
+    rank: bool,
+
+    /// Also match words within edit distance (like `word~N` for every word)
+    #[arg(long, default_value = "0", value_name = "N")]
+    fuzzy: usize,
+
+    /// Build trigram index (required for substring and regex search)
+    #[arg(long, default_value = "false")]
+    trigrams: bool,
+
+    /// Store word positions in index to evaluate phrases and words order without reading log
+    #[arg(long, default_value = "false")]
+    positions: bool,
+
+    /// Treat query as case-insensitive substring
+    #[arg(long, default_value = "false", conflicts_with = "regex")]
+    substring: bool,
+
+    /// Treat query as regular expression
+    #[arg(long, default_value = "false")]
+    regex: bool,
+
+    /// Keep only lines matching regular expression, capture groups are highlighted
+    #[arg(short, long = "match", value_name = "REGEX")]
+    match_regex: Option<String>,
+
     /// List of words to search (prefix match)
     #[arg(required = true)]
     words: Vec<String>,
 }
 
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    /// Colored text
+    Text,
+    /// JSON object per matched line
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List indexed terms with number of lines containing them
+    Terms(TermsArgs),
+    /// Search logs interactively, query is re-run on every keystroke
+    Tui(TuiArgs),
+}
+
 fn main() -> anyhow::Result<()> {
-    run_on_path(&Cli::parse())?;
+    let cli = Cli::parse();
+    match &cli.command {
+        Some(Command::Terms(args)) => run_terms(args)?,
+        Some(Command::Tui(args)) => run_tui(args)?,
+        None => run_on_path(&cli)?,
+    }
     Ok(())
 }
 
 fn run_on_path(args: &Cli) -> anyhow::Result<()> {
-    let log_paths = resolve_log_files(vec![args.path.clone()])?;
+    let Some(path) = &args.path else {
+        anyhow::bail!("Missing path");
+    };
+    let log_paths = resolve_log_files(vec![path.clone()])?;
+    if args.follow && log_paths.len() != 1 {
+        anyhow::bail!("Follow mode requires single log file");
+    }
+    // Indexing progress is shown before pager takes the terminal
+    for log_path in &log_paths {
+        check_index(log_path.clone(), args.force_reindex, ix_options(args))?;
+    }
+    let _pager = if args.no_pager || args.follow {
+        None
+    } else {
+        start_pager()?
+    };
+    if args.merge {
+        return run_merged(args, log_paths);
+    }
     let print_header = log_paths.len() > 1;
+    let with_filename = args.with_filename || print_header && args.no_heading;
     let mut is_first = true;
+    let mut total = 0;
+    let mut histogram = match &args.histogram {
+        Some(bucket) => Some(Histogram::new(parse_duration(bucket)?)),
+        None => None,
+    };
+    let list_files = args.files_with_matches || args.files_without_match;
+    // One printer for all files, so blocks of different files are separated like in grep
+    let mut printer = line_printer(args, !args.reverse && !args.rank);
     for log_path in log_paths {
-        if print_header {
+        if print_header
+            && !list_files
+            && !with_filename
+            && !args.count
+            && histogram.is_none()
+            && args.output == OutputFormat::Text
+        {
             if !is_first {
                 println!();
             } else {
                 is_first = false;
             }
+            printer.start_file();
             println!("{}:", log_path.display());
             println!();
         }
-        check_index(log_path.clone(), args.force_reindex)?;
-        run_on_file(args, log_path)?;
+        if list_files {
+            if has_match(args, log_path.clone())? == args.files_with_matches {
+                println!("{}", printer.file_prefix(&log_path, 0));
+            }
+            continue;
+        }
+        let matched = run_on_file(
+            args,
+            log_path.clone(),
+            histogram.as_mut(),
+            with_filename,
+            &mut printer,
+        )?;
+        if args.count {
+            if print_header {
+                println!("{}:{matched}", log_path.display());
+            } else {
+                println!("{matched}");
+            }
+        }
+        total += matched;
+    }
+    if args.count && print_header {
+        println!("total:{total}");
+    }
+    if let Some(histogram) = histogram {
+        histogram.print();
     }
     Ok(())
 }
 
-fn run_on_file(args: &Cli, log_path: PathBuf) -> anyhow::Result<()> {
-    let Some(query) = Query::parse(&args.words.join(" ")) else {
+fn ix_options(args: &Cli) -> IxOptions {
+    IxOptions {
+        trigrams: args.trigrams || args.substring || args.regex,
+        positions: args.positions,
+    }
+}
+
+/// Prints matched lines of all logs in timestamp order, each line is prefixed with its log path
+fn run_merged(args: &Cli, log_paths: Vec<PathBuf>) -> anyhow::Result<()> {
+    let query = parse_query(args)?;
+    if query.is_none() && !args.regex && !args.substring {
         return Ok(());
-    };
-    let ix = IxReader::new(log_path)?;
-    if args.debug_print {
-        ix.print_debug();
     }
-    let mut lines = ix.query(&query, args.whole_words)?;
+    let mut printer = line_printer(args, true);
+    let mut sources = Vec::new();
+    let mut prefixes = Vec::new();
+    for (i, log_path) in log_paths.iter().enumerate() {
+        let ix = IxReader::new(log_path.clone())?;
+        let (filters, terms) = line_filters(args, query.as_ref(), &ix)?;
+        let lines = query_lines(args, query.as_ref(), &ix, &filters, false)?;
+        sources.push(MergeSource::new(ix, lines, filters, terms));
+        prefixes.push(printer.file_prefix(log_path, i));
+    }
     let before = args.before.max(args.context);
     let after = args.after.max(args.context);
-    let words = query.get_words();
+    let mut merger = LinesMerger::new(sources, before, after)?;
+    let mut printed = 0;
     let mut tail_lines = VecDeque::new();
+    while let Some((i, offset, line)) = merger.next()? {
+        if args.tail > 0 {
+            tail_lines.push_back((i, offset, line));
+            if tail_lines.len() > args.tail {
+                tail_lines.pop_front();
+            }
+            continue;
+        }
+        let source = &merger.sources[i];
+        print_matched_line(
+            &mut printer,
+            &log_paths[i],
+            offset,
+            line,
+            &source.terms,
+            &source.filters,
+            &prefixes[i],
+        )?;
+        printed += 1;
+        if args.head > 0 && printed >= args.head {
+            break;
+        }
+    }
+    for (i, offset, line) in tail_lines {
+        let source = &merger.sources[i];
+        print_matched_line(
+            &mut printer,
+            &log_paths[i],
+            offset,
+            line,
+            &source.terms,
+            &source.filters,
+            &prefixes[i],
+        )?;
+    }
+    printer.finish();
+    Ok(())
+}
+
+fn line_printer(args: &Cli, in_log_order: bool) -> LinePrinter {
+    let colors = match args.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            (std::io::stdout().is_terminal() || pager::is_active())
+                && std::env::var_os("NO_COLOR").is_none_or(|x| x.is_empty())
+        }
+    };
+    LinePrinter::new(
+        args.output == OutputFormat::Json,
+        colors,
+        args.before.max(args.after).max(args.context) > 0,
+        args.only_matching,
+        in_log_order,
+    )
+}
+
+/// Returns number of matched lines
+fn run_on_file(
+    args: &Cli,
+    log_path: PathBuf,
+    histogram: Option<&mut Histogram>,
+    with_filename: bool,
+    printer: &mut LinePrinter,
+) -> anyhow::Result<usize> {
+    let query = parse_query(args)?;
+    if query.is_none() && !args.regex && !args.substring {
+        return Ok(0);
+    }
+    let ix = IxReader::new(log_path.clone())?;
+    if args.debug_print {
+        ix.print_debug();
+    }
+    let options = query_options(args);
+    let (filters, terms) = line_filters(args, query.as_ref(), &ix)?;
+    let query_lines = |reverse: bool| query_lines(args, query.as_ref(), &ix, &filters, reverse);
+    if args.rank
+        && let Some(query) = &query
+    {
+        let ranked = rank_lines(&ix, &mut query_lines(false)?, &query.terms(), options)?;
+        return print_ranked(args, &ix, ranked, &terms, &filters, with_filename, printer);
+    }
+    // Lines in output order
+    let mut lines = query_lines(args.reverse)?;
     if args.debug_print {
         lines.print_debug(0);
     }
-    let mut show_separator = false;
-    let mut processed = 0;
+    if args.count {
+        return count_lines(&ix, &mut lines, &filters);
+    }
+    if let Some(histogram) = histogram {
+        return collect_histogram(&ix, &mut lines, &filters, histogram);
+    }
+    let before = args.before.max(args.context);
+    let after = args.after.max(args.context);
+    let prefix = if with_filename {
+        printer.file_prefix(&log_path, 0)
+    } else {
+        String::new()
+    };
+    let mut printed = 0;
+    let mut last_head_offset = None;
     let head_requested = args.head > 0;
     let tail_requested = args.tail > 0;
-    let check_order = if args.order_important {
-        Some(&query)
-    } else {
+    if head_requested || !tail_requested {
+        while let Some(line_offset) = lines.next()? {
+            let Some(line) = ix.read_log(line_offset, before, after, &filters)? else {
+                continue;
+            };
+            print_matched_line(
+                printer,
+                &log_path,
+                line_offset,
+                line,
+                &terms,
+                &filters,
+                &prefix,
+            )?;
+            printed += 1;
+            last_head_offset = Some(line_offset);
+            if head_requested && printed >= args.head {
+                break;
+            }
+        }
+    }
+    if tail_requested {
+        // Read from the other end, stop at lines printed by head
+        let mut lines = query_lines(!args.reverse)?;
+        let mut tail_lines = Vec::new();
+        while tail_lines.len() < args.tail {
+            let Some(line_offset) = lines.next()? else {
+                break;
+            };
+            if Some(line_offset) == last_head_offset {
+                break;
+            }
+            if filters.is_empty() || ix.read_log(line_offset, 0, 0, &filters)?.is_some() {
+                tail_lines.push(line_offset);
+            }
+        }
+        for line_offset in tail_lines.into_iter().rev() {
+            if let Some(line) = ix.read_log(line_offset, before, after, &[])? {
+                print_matched_line(
+                    printer,
+                    &log_path,
+                    line_offset,
+                    line,
+                    &terms,
+                    &filters,
+                    &prefix,
+                )?;
+                printed += 1;
+            }
+        }
+    }
+    printer.finish();
+    if args.follow {
+        follow_log(&log_path, &ix, |line_offset, line| {
+            let tokens = parse_words(line);
+            let matched = query.as_ref().is_none_or(|query| {
+                query.check_line(&tokens, options)
+                    && (!args.order_important || query.check_words_order(&tokens))
+            });
+            if matched && filters.iter().all(|filter| filter.check(line)) {
+                // Appended lines can contain words missing in index dictionary
+                let mut terms = terms.clone();
+                if let Some(query) = &query {
+                    terms.extend(
+                        tokens
+                            .into_iter()
+                            .filter(|token| query.matches_token(token, options)),
+                    );
+                }
+                let line = LogLine {
+                    line: line.to_string(),
+                    before: Vec::new(),
+                    after: Vec::new(),
+                    end_offset: line_offset + line.len() as u64,
+                };
+                print_matched_line(
+                    printer,
+                    &log_path,
+                    line_offset,
+                    line,
+                    &terms,
+                    &filters,
+                    &prefix,
+                )?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(printed)
+}
+
+/// Stops at first matched line, log is read only if candidate lines have to be verified by filters
+fn has_match(args: &Cli, log_path: PathBuf) -> anyhow::Result<bool> {
+    let query = parse_query(args)?;
+    if query.is_none() && !args.regex && !args.substring {
+        return Ok(false);
+    }
+    let ix = IxReader::new(log_path)?;
+    let (filters, _) = line_filters(args, query.as_ref(), &ix)?;
+    let mut lines = query_lines(args, query.as_ref(), &ix, &filters, false)?;
+    while let Some(line_offset) = lines.next()? {
+        if filters.is_empty() || ix.read_log(line_offset, 0, 0, &filters)?.is_some() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Query of words mode, parsed once for all files
+fn parse_query(args: &Cli) -> anyhow::Result<Option<Query>> {
+    let pattern = args.words.join(" ");
+    Ok(if args.regex || args.substring {
         None
+    } else if args.any_terms {
+        Query::parse(&pattern)?.map(Query::into_any_terms)
+    } else {
+        Query::parse(&pattern)?
+    })
+}
+
+fn query_options(args: &Cli) -> QueryOptions {
+    QueryOptions {
+        whole_words: args.whole_words,
+        order_important: args.order_important,
+        fuzzy: args.fuzzy,
+        reverse: false,
+    }
+}
+
+/// Returns filters verifying candidate lines selected by `ix` and dictionary terms to highlight
+fn line_filters<'a>(
+    args: &Cli,
+    query: Option<&'a Query>,
+    ix: &IxReader,
+) -> anyhow::Result<(Vec<LineFilter<'a>>, HashSet<String>)> {
+    let pattern = args.words.join(" ");
+    let mut filters = Vec::new();
+    if let Some(match_regex) = &args.match_regex {
+        filters.push(LineFilter::Regex(Regex::new(match_regex)?));
+    }
+    if args.since.is_some() || args.until.is_some() {
+        filters.push(LineFilter::TimeRange(
+            parse_time_arg(&args.since)?,
+            parse_time_arg(&args.until)?,
+        ));
+    }
+    // Filters verifying query match, regex and substring matches are highlighted by them
+    let mut checks = Vec::new();
+    let terms = if args.regex {
+        checks.push(LineFilter::Regex(Regex::new(&pattern)?));
+        HashSet::new()
+    } else if args.substring {
+        checks.push(LineFilter::with_substring(&pattern));
+        HashSet::new()
+    } else if let Some(query) = query {
+        if query.has_proximity() && !ix.has_positions {
+            checks.push(LineFilter::Proximity(query, query_options(args)));
+        }
+        if args.order_important && !ix.has_positions {
+            checks.push(LineFilter::WordsOrder(query));
+        }
+        ix.expand_terms(query, query_options(args))
+    } else {
+        HashSet::new()
     };
+    if !args.invert_match {
+        filters.extend(checks);
+        return Ok((filters, terms));
+    }
+    // Index selects exactly matched lines when there is nothing to verify,
+    // otherwise all lines are scanned and checked by inverted filter
+    if !checks.is_empty() {
+        filters.push(LineFilter::Inverted(query, query_options(args), checks));
+    }
+    Ok((filters, HashSet::new()))
+}
+
+/// Selects candidate lines of log by index, lines not selected by index for inverted match
+fn query_lines(
+    args: &Cli,
+    query: Option<&Query>,
+    ix: &IxReader,
+    filters: &[LineFilter],
+    reverse: bool,
+) -> anyhow::Result<LinesReader> {
+    if args.invert_match {
+        let all_lines = LinesReader::with_scan(&ix.log_path, reverse)?;
+        if filters
+            .iter()
+            .any(|filter| matches!(filter, LineFilter::Inverted(..)))
+        {
+            return Ok(all_lines);
+        }
+        let matched = matched_lines(args, query, ix, reverse)?;
+        return LinesReader::with_except(all_lines, matched, reverse);
+    }
+    matched_lines(args, query, ix, reverse)
+}
+
+/// Selects lines matching query by index, they may need verification by filters
+fn matched_lines(
+    args: &Cli,
+    query: Option<&Query>,
+    ix: &IxReader,
+    reverse: bool,
+) -> anyhow::Result<LinesReader> {
+    let pattern = args.words.join(" ");
+    if args.regex {
+        ix.query_trigrams(regex_query(&pattern)?.as_ref(), reverse)
+    } else if args.substring {
+        ix.query_trigrams(substring_query(&pattern).as_ref(), reverse)
+    } else if let Some(query) = query {
+        ix.query(
+            query,
+            QueryOptions {
+                reverse,
+                ..query_options(args)
+            },
+        )
+    } else {
+        Ok(LinesReader::Empty)
+    }
+}
+
+/// Counts by index only, log is read only to check lines by filters
+fn count_lines(
+    ix: &IxReader,
+    lines: &mut LinesReader,
+    filters: &[LineFilter],
+) -> anyhow::Result<usize> {
+    let mut count = 0;
     while let Some(line_offset) = lines.next()? {
-        let line = if check_order.is_none() {
-            None
-        } else if let Some(line) = ix.read_log(line_offset, before, after, check_order)? {
-            Some(line)
-        } else {
+        if filters.is_empty() || ix.read_log(line_offset, 0, 0, filters)?.is_some() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Prints top lines passing filters in relevance order, returns number of printed lines
+fn print_ranked(
+    args: &Cli,
+    ix: &IxReader,
+    ranked: Vec<(u64, f64)>,
+    terms: &HashSet<String>,
+    filters: &[LineFilter],
+    with_filename: bool,
+    printer: &mut LinePrinter,
+) -> anyhow::Result<usize> {
+    let limit = if args.head > 0 { args.head } else { 10 };
+    let before = args.before.max(args.context);
+    let after = args.after.max(args.context);
+    let prefix = if with_filename {
+        printer.file_prefix(&ix.log_path, 0)
+    } else {
+        String::new()
+    };
+    let mut printed = 0;
+    for (line_offset, score) in ranked {
+        if printed >= limit {
+            break;
+        }
+        let Some(line) = ix.read_log(line_offset, 0, 0, filters)? else {
             continue;
         };
-        if head_requested && processed < args.head || !tail_requested {
-            let line = if let Some(line) = line {
-                line
-            } else {
-                ix.read_log(line_offset, before, after, None)?.unwrap()
-            };
-            print_line(line, &words, &mut show_separator)?;
-        }
-        if tail_requested && (!head_requested || processed >= args.head) {
-            tail_lines.push_back(line_offset);
-            if tail_lines.len() > args.tail {
-                tail_lines.pop_front();
-            }
+        if args.debug_print {
+            println!("Score: {score:.3}");
         }
-        processed += 1;
-        if !tail_requested && head_requested && processed >= args.head {
-            break;
+        let line = if before > 0 || after > 0 {
+            ix.read_log(line_offset, before, after, &[])?.unwrap()
+        } else {
+            line
+        };
+        print_matched_line(
+            printer,
+            &ix.log_path,
+            line_offset,
+            line,
+            terms,
+            filters,
+            &prefix,
+        )?;
+        printed += 1;
+    }
+    printer.finish();
+    Ok(printed)
+}
+
+fn print_matched_line(
+    printer: &mut LinePrinter,
+    log_path: &Path,
+    line_offset: u64,
+    line: LogLine,
+    terms: &HashSet<String>,
+    filters: &[LineFilter],
+    prefix: &str,
+) -> anyhow::Result<()> {
+    let spans = match_spans(&line.line, terms, filters);
+    printer.print(log_path, line_offset, line, &spans, prefix)
+}
+
+/// Sorted non-overlapping byte ranges of line tokens found in `terms` and of filter matches
+pub fn match_spans(
+    line: &str,
+    terms: &HashSet<String>,
+    filters: &[LineFilter],
+) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = parse_word_spans(line)
+        .into_iter()
+        .filter(|(_, token)| terms.contains(token))
+        .map(|(span, _)| span)
+        .collect();
+    for filter in filters {
+        spans.extend(filter.highlights(line));
+    }
+    spans.sort_by_key(|span| (span.start, std::cmp::Reverse(span.end)));
+    let mut result: Vec<Range<usize>> = Vec::new();
+    for span in spans {
+        match result.last_mut() {
+            Some(last) if span.start < last.end => last.end = last.end.max(span.end),
+            _ => result.push(span),
         }
     }
-    for line_offset in tail_lines {
-        if let Some(line) = ix.read_log(line_offset, before, after, None)? {
-            print_line(line, &words, &mut show_separator)?;
+    result
+}
+
+fn collect_histogram(
+    ix: &IxReader,
+    lines: &mut LinesReader,
+    filters: &[LineFilter],
+    histogram: &mut Histogram,
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+    while let Some(line_offset) = lines.next()? {
+        if let Some(line) = ix.read_log(line_offset, 0, 0, filters)? {
+            histogram.add(parse_line_timestamp(&line.line));
+            count += 1;
         }
     }
-    Ok(())
+    Ok(count)
+}
+
+fn parse_time_arg(time: &Option<String>) -> anyhow::Result<Option<i64>> {
+    time.as_ref()
+        .map(|time| parse_timestamp(time).ok_or_else(|| anyhow::anyhow!("Invalid time: {time}")))
+        .transpose()
 }
 
-pub fn check_index(log_path: PathBuf, force_reindex: bool) -> anyhow::Result<()> {
+/// Builds index if it is missing, has no requested features or log was changed.
+/// If log has grown only appended lines are indexed.
+pub fn check_index(
+    log_path: PathBuf,
+    force_reindex: bool,
+    mut options: IxOptions,
+) -> anyhow::Result<()> {
     let ix_path = ix_path(log_path.clone())?;
+    let log_size = std::fs::metadata(&log_path)?.len();
     if ix_path.exists() {
-        if force_reindex {
-            std::fs::remove_file(&ix_path)?;
-        } else {
-            return Ok(());
+        // Index with unreadable (truncated or corrupt) header is rebuilt
+        if !force_reindex && let Ok(header) = IxHeaderSection::read(&mut File::open(&ix_path)?) {
+            // Log replaced by another file is reindexed even if it is bigger
+            if options.satisfied_by(&header)
+                && header.log_size <= log_size
+                && header.head_hash == hash_bytes(&read_log_head(&log_path, header.log_size)?)
+            {
+                // Unterminated last line is indexed when it is complete
+                if !has_complete_line(&log_path, header.log_size)? {
+                    return Ok(());
+                }
+                let ix = IxReader::new(log_path.clone())?;
+                return index_log(&log_path, IxBuilder::from_reader(&ix)?, &ix_path);
+            }
+            // Keep features of existing index
+            options.trigrams |= header.has_trigrams();
+            options.positions |= header.positions;
+        }
+        std::fs::remove_file(&ix_path)?;
+    }
+    index_log(&log_path, IxBuilder::new(options), &ix_path)
+}
+
+/// Returns true if log has a line ending after `offset`
+fn has_complete_line(log_path: &Path, offset: u64) -> anyhow::Result<bool> {
+    let mut log_file = File::open(log_path)?;
+    log_file.seek(SeekFrom::Start(offset))?;
+    let mut log_reader = BufReader::new(log_file);
+    loop {
+        let buf = log_reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(false);
         }
+        if buf.contains(&b'\n') {
+            return Ok(true);
+        }
+        let len = buf.len();
+        log_reader.consume(len);
     }
+}
 
-    let log_file = File::open(&log_path)?;
+/// Indexes log lines starting from end of lines already added to builder
+fn index_log(log_path: &Path, mut ix_builder: IxBuilder, ix_path: &Path) -> anyhow::Result<()> {
+    let mut log_file = File::open(log_path)?;
     let log_size = log_file.metadata()?.len();
+    let mut line_offset = ix_builder.log_size;
+    log_file.seek(SeekFrom::Start(line_offset))?;
     let mut log_reader = BufReader::new(log_file);
-    let mut ix_builder = IxBuilder::new();
-    let mut line_offset = 0u64;
     let mut last_percent = 0;
     loop {
-        let percent = line_offset * 100 / log_size;
+        let percent = line_offset * 100 / log_size.max(1);
         if percent != last_percent {
-            print!("\rIndexing: {}%\x1b[K", percent);
-            std::io::stdout().flush()?;
+            eprint!("\rIndexing: {}%\x1b[K", percent);
+            std::io::stderr().flush()?;
             last_percent = percent;
         }
         let mut line: String = String::new();
         let len = log_reader.read_line(&mut line)? as u64;
-        if len == 0 {
+        // Unterminated last line is indexed when it is complete
+        if len == 0 || !line.ends_with('\n') {
             break;
         };
-        for token in parse_words(&line) {
-            ix_builder.add_word(token, line_offset);
-        }
+        ix_builder.add_line(&line, line_offset);
         line_offset += len;
     }
-    print!("\rWriting index...\x1b[K");
-    let ix_file = File::create(ix_path)?;
-    ix_builder.write(&mut BufWriter::new(ix_file))?;
-    print!("\r\x1b[K");
+    eprint!("\rWriting index...\x1b[K");
+    ix_builder.write_file(ix_path)?;
+    eprint!("\r\x1b[K");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_lines(ix: &IxReader, word: &str) -> Vec<u64> {
+        let options = QueryOptions {
+            whole_words: true,
+            ..QueryOptions::default()
+        };
+        let mut lines = ix.query(&Query::Word(word.to_string()), options).unwrap();
+        let mut offsets = Vec::new();
+        while let Some(offset) = lines.next().unwrap() {
+            offsets.push(offset);
+        }
+        offsets
+    }
+
+    #[test]
+    fn unterminated_last_line_is_searched_and_indexed_when_complete() {
+        let dir = std::env::temp_dir().join(format!("qx-check-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("tail.log");
+        std::fs::write(&log_path, "first line\npartial TIMEO").unwrap();
+        let ix_path = ix_path(log_path.clone()).unwrap();
+        let modified = || std::fs::metadata(&ix_path).unwrap().modified().unwrap();
+
+        check_index(log_path.clone(), false, IxOptions::default()).unwrap();
+        let ix = IxReader::new(log_path.clone()).unwrap();
+        assert_eq!((ix.lines_count, ix.log_size), (1, 11));
+        assert_eq!(word_lines(&ix, "FIRST"), vec![0]);
+        assert_eq!(word_lines(&ix, "PARTIAL"), vec![11]);
+        assert!(ix.words_section.select_words("PARTIAL", true).is_empty());
+        let first_write = modified();
+        check_index(log_path.clone(), false, IxOptions::default()).unwrap();
+        assert_eq!(modified(), first_write, "index is not written again");
+
+        let mut log_file = File::options().append(true).open(&log_path).unwrap();
+        log_file.write_all(b"UT done\nlast").unwrap();
+        check_index(log_path.clone(), false, IxOptions::default()).unwrap();
+        let ix = IxReader::new(log_path.clone()).unwrap();
+        assert_eq!((ix.lines_count, ix.log_size), (2, 32));
+        assert_eq!(word_lines(&ix, "PARTIAL"), vec![11]);
+        assert_eq!(word_lines(&ix, "TIMEOUT"), vec![11]);
+        assert!(word_lines(&ix, "TIMEO").is_empty());
+        assert_eq!(word_lines(&ix, "LAST"), vec![32]);
+        assert!(ix.words_section.select_words("LAST", true).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn index_with_truncated_header_is_rebuilt() {
+        let dir = std::env::temp_dir().join(format!("qx-corrupt-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("corrupt.log");
+        std::fs::write(&log_path, "first line\n").unwrap();
+        std::fs::write(ix_path(log_path.clone()).unwrap(), [0, 6, 0]).unwrap();
+        check_index(log_path.clone(), false, IxOptions::default()).unwrap();
+        let ix = IxReader::new(log_path.clone()).unwrap();
+        assert_eq!(word_lines(&ix, "FIRST"), vec![0]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,93 @@
+use crate::file_utils::LogLine;
+use crate::filter::LineFilter;
+use crate::index::{IxReader, LinesReader};
+use crate::time_utils::parse_line_timestamp_nanos;
+use std::collections::HashSet;
+
+/// Matched lines of one log file
+pub struct MergeSource<'a> {
+    ix: IxReader,
+    lines: LinesReader,
+    pub filters: Vec<LineFilter<'a>>,
+    /// Dictionary terms to highlight
+    pub terms: HashSet<String>,
+    /// Next matched line: (timestamp as seconds and nanoseconds, offset, line)
+    current: Option<((i64, u32), u64, LogLine)>,
+    last_timestamp: (i64, u32),
+}
+
+impl<'a> MergeSource<'a> {
+    pub fn new(
+        ix: IxReader,
+        lines: LinesReader,
+        filters: Vec<LineFilter<'a>>,
+        terms: HashSet<String>,
+    ) -> Self {
+        Self {
+            ix,
+            lines,
+            filters,
+            terms,
+            current: None,
+            last_timestamp: (i64::MIN, 0),
+        }
+    }
+
+    /// Reads next matched line, line without timestamp takes timestamp of previous one
+    fn advance(&mut self, before: usize, after: usize) -> anyhow::Result<()> {
+        self.current = None;
+        while let Some(offset) = self.lines.next()? {
+            if let Some(line) = self.ix.read_log(offset, before, after, &self.filters)? {
+                if let Some(timestamp) = parse_line_timestamp_nanos(&line.line) {
+                    self.last_timestamp = timestamp;
+                }
+                self.current = Some((self.last_timestamp, offset, line));
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Merges matched lines of several logs in timestamp order, lines with equal timestamps go in order of sources
+pub struct LinesMerger<'a> {
+    pub sources: Vec<MergeSource<'a>>,
+    before: usize,
+    after: usize,
+}
+
+impl<'a> LinesMerger<'a> {
+    pub fn new(
+        mut sources: Vec<MergeSource<'a>>,
+        before: usize,
+        after: usize,
+    ) -> anyhow::Result<Self> {
+        for source in &mut sources {
+            source.advance(before, after)?;
+        }
+        Ok(Self {
+            sources,
+            before,
+            after,
+        })
+    }
+
+    /// Returns index of source, offset and line
+    pub fn next(&mut self) -> anyhow::Result<Option<(usize, u64, LogLine)>> {
+        let next = self
+            .sources
+            .iter()
+            .enumerate()
+            .filter_map(|(i, source)| {
+                let (timestamp, _, _) = source.current.as_ref()?;
+                Some((*timestamp, i))
+            })
+            .min();
+        let Some((_, i)) = next else {
+            return Ok(None);
+        };
+        let (_, offset, line) = self.sources[i].current.take().unwrap();
+        self.sources[i].advance(self.before, self.after)?;
+        Ok(Some((i, offset, line)))
+    }
+}
@@ -0,0 +1,94 @@
+use std::process::Child;
+#[cfg(unix)]
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const DEFAULT_PAGER: &str = "less -R";
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Output goes to pager showing it on terminal
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Pager process reading stdout, waited for when dropped so it keeps the terminal until closed
+pub struct Pager {
+    child: Child,
+    #[cfg(unix)]
+    saved_stdout: std::os::fd::OwnedFd,
+}
+
+/// Starts pager set by `QX_PAGER` or `PAGER` (empty value disables paging) if stdout is terminal.
+/// Unless `LESS` is set, less prints output fitting the screen without paging.
+#[cfg(unix)]
+pub fn start_pager() -> anyhow::Result<Option<Pager>> {
+    use std::io::IsTerminal;
+    if !std::io::stdout().is_terminal() {
+        return Ok(None);
+    }
+    let pager = std::env::var("QX_PAGER")
+        .or_else(|_| std::env::var("PAGER"))
+        .unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next() else {
+        return Ok(None);
+    };
+    let mut command = Command::new(program);
+    command.args(words).stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    // Output is printed directly if pager is not installed
+    let Ok(mut child) = command.spawn() else {
+        return Ok(None);
+    };
+    let saved_stdout = redirect_stdout(child.stdin.take().unwrap())?;
+    ACTIVE.store(true, Ordering::Relaxed);
+    Ok(Some(Pager {
+        child,
+        saved_stdout,
+    }))
+}
+
+/// Output is not paged on platforms without file descriptors
+#[cfg(not(unix))]
+pub fn start_pager() -> anyhow::Result<Option<Pager>> {
+    Ok(None)
+}
+
+/// Replaces stdout with `stdin` of pager, returns original stdout
+#[cfg(unix)]
+fn redirect_stdout(stdin: std::process::ChildStdin) -> anyhow::Result<std::os::fd::OwnedFd> {
+    use std::io::Write;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    std::io::stdout().flush()?;
+    // SAFETY: both descriptors are open, duplicated stdout is owned by returned value
+    unsafe {
+        let saved_stdout = libc::dup(libc::STDOUT_FILENO);
+        if saved_stdout < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let saved_stdout = OwnedFd::from_raw_fd(saved_stdout);
+        if libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // Quitting pager before end of output ends qx quietly instead of failing to print
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+        Ok(saved_stdout)
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+        // Pager gets end of input only when stdout does not refer to its pipe anymore
+        #[cfg(unix)]
+        unsafe {
+            use std::os::fd::AsRawFd;
+            libc::dup2(self.saved_stdout.as_raw_fd(), libc::STDOUT_FILENO);
+        }
+        let _ = self.child.wait();
+    }
+}
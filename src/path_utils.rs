@@ -27,7 +27,7 @@ fn decompress_logs<R: Read>(
             let file_name = file_name.to_string_lossy().to_string();
             if file_name.contains(".log") {
                 let log_path = PathBuf::from(format!("{dest_base_path}-{file_name}"));
-                println!(
+                eprintln!(
                     "Extracting {file_name} from {} to {}",
                     arc_path.as_ref().to_string_lossy(),
                     log_path.to_string_lossy()
@@ -40,7 +40,7 @@ fn decompress_logs<R: Read>(
     if !extracted.is_empty() {
         std::fs::remove_file(arc_path)?;
     } else {
-        println!(
+        eprintln!(
             "{} does not contain log files",
             arc_path.as_ref().to_string_lossy()
         );
@@ -81,13 +81,13 @@ fn collect_files<const N: usize>(dir: &Path, substr: [&str; N]) -> anyhow::Resul
 
             if path.is_dir() {
                 collected.extend(collect_files(&path, substr)?);
-            } else if let Some(filename) = path.file_name().and_then(|f| f.to_str()) {
-                if substr.iter().any(|suffix| {
+            } else if let Some(filename) = path.file_name().and_then(|f| f.to_str())
+                && substr.iter().any(|suffix| {
                     filename.to_lowercase().contains(&suffix.to_lowercase())
                         && !filename.to_lowercase().ends_with(".ix")
-                }) {
-                    collected.push(path);
-                }
+                })
+            {
+                collected.push(path);
             }
         }
     }
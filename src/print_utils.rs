@@ -1,58 +1,246 @@
-use crate::file_utils::LogLine;
+use crate::file_utils::{LineNumbers, LogLine};
 use colorize::AnsiColor;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
-pub fn print_line(
-    line: LogLine,
-    highlight_words: &[String],
-    show_separator: &mut bool,
-) -> anyhow::Result<()> {
-    if !line.before.is_empty() || !line.after.is_empty() {
-        if *show_separator {
-            println!("--");
-        } else {
-            *show_separator = true;
+/// Prints matched lines as text or as JSON lines
+pub struct LinePrinter {
+    json: bool,
+    colors: bool,
+    /// Context lines were requested, blocks of matches with context are separated by `--`
+    context: bool,
+    show_separator: bool,
+    in_log_order: bool,
+    /// Context after last printed match, held back until next match shows if it continues the block
+    pending: Option<PendingContext>,
+    /// Print only matched parts of lines, each on its own line
+    only_matching: bool,
+    /// Line numbers are counted only if lines are printed in log order
+    line_numbers: Option<HashMap<PathBuf, LineNumbers>>,
+}
+
+impl LinePrinter {
+    pub fn new(
+        json: bool,
+        colors: bool,
+        context: bool,
+        only_matching: bool,
+        in_log_order: bool,
+    ) -> Self {
+        Self {
+            json,
+            // colorize prints color reset at exit if it was used, JSON output must stay clean
+            colors: colors && !json,
+            context,
+            show_separator: false,
+            in_log_order,
+            pending: None,
+            only_matching,
+            line_numbers: (json && in_log_order).then(HashMap::new),
         }
     }
-    for line in line.before {
-        println!("{}", line.b_grey());
+
+    /// Log path to print before lines, colored differently for each file
+    pub fn file_prefix(&self, log_path: &Path, index: usize) -> String {
+        let prefix = log_path.display().to_string();
+        if !self.colors {
+            return prefix;
+        }
+        match index % 5 {
+            0 => prefix.magenta(),
+            1 => prefix.cyan(),
+            2 => prefix.green(),
+            3 => prefix.yellow(),
+            _ => prefix.blue(),
+        }
     }
-    println!(
-        "{}",
-        crate::print_utils::highlight_words(line.line.as_str(), &highlight_words)
-    );
-    for line in line.after {
-        println!("{}", line.b_grey());
+
+    /// Prints matched line, `spans` are sorted non-overlapping byte ranges of matched parts
+    pub fn print(
+        &mut self,
+        log_path: &Path,
+        offset: u64,
+        line: LogLine,
+        spans: &[Range<usize>],
+        prefix: &str,
+    ) -> anyhow::Result<()> {
+        if !self.json && self.only_matching {
+            let prefix = if prefix.is_empty() {
+                String::new()
+            } else {
+                format!("{prefix}:")
+            };
+            for span in spans {
+                let text = line.line[span.clone()].to_string();
+                let text = if self.colors { text.red() } else { text };
+                println!("{prefix}{text}");
+            }
+            return Ok(());
+        }
+        if !self.json {
+            self.print_text(log_path, offset, line, spans, prefix);
+            return Ok(());
+        }
+        let line_number = match &mut self.line_numbers {
+            Some(line_numbers) => match line_numbers.get_mut(log_path) {
+                Some(line_numbers) => line_numbers.line_number(offset)?,
+                None => line_numbers
+                    .entry(log_path.to_path_buf())
+                    .or_insert(LineNumbers::new(log_path)?)
+                    .line_number(offset)?,
+            },
+            None => None,
+        };
+        let matches: Vec<String> = spans
+            .iter()
+            .map(|span| {
+                format!(
+                    "{{\"text\":{},\"start\":{},\"end\":{}}}",
+                    json_string(&line.line[span.clone()]),
+                    line.line[..span.start].chars().count(),
+                    line.line[..span.end].chars().count(),
+                )
+            })
+            .collect();
+        println!(
+            "{{\"path\":{},\"offset\":{offset},\"line_number\":{},\"line\":{},\"before\":{},\"after\":{},\"matches\":[{}]}}",
+            json_string(&log_path.to_string_lossy()),
+            line_number.map_or("null".to_string(), |x| x.to_string()),
+            json_string(&line.line),
+            json_strings(&line.before),
+            json_strings(&line.after),
+            matches.join(","),
+        );
+        Ok(())
     }
-    Ok(())
-}
 
-pub fn highlight_words(line: &str, words: &[String]) -> String {
-    let lowercase_line = line.to_lowercase();
-    let lowercase_words: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+    /// Prints matched line with context, non-empty `prefix` is printed before each line
+    /// followed by `:` for matched line and `-` for context lines (like grep does).
+    /// Overlapping and adjacent context of lines printed in log order is merged into one block.
+    fn print_text(
+        &mut self,
+        log_path: &Path,
+        offset: u64,
+        line: LogLine,
+        spans: &[Range<usize>],
+        prefix: &str,
+    ) {
+        let (match_prefix, context_prefix) = if prefix.is_empty() {
+            (String::new(), String::new())
+        } else {
+            (format!("{prefix}:"), format!("{prefix}-"))
+        };
+        let start_offset = line.before.first().map_or(offset, |(x, _)| *x);
+        let continued = self.pending.as_ref().is_some_and(|pending| {
+            self.in_log_order
+                && pending.log_path == log_path
+                && pending.match_offset < offset
+                && start_offset <= pending.end_offset
+        });
+        // Offset of last line printed in continued block
+        let mut printed_offset = 0;
+        if let Some(pending) = self.pending.take() {
+            printed_offset = pending.match_offset;
+            for (line_offset, context_line) in pending.after {
+                // Lines from matched one are printed by this block
+                if continued && line_offset >= offset {
+                    break;
+                }
+                self.print_context(&pending.context_prefix, context_line);
+                printed_offset = line_offset;
+            }
+        }
+        // Like grep, separator is printed even if match has no context lines at start or end of log
+        if !continued && self.context {
+            if self.show_separator {
+                println!("--");
+            } else {
+                self.show_separator = true;
+            }
+        }
+        for (line_offset, context_line) in line.before {
+            if !continued || line_offset > printed_offset {
+                self.print_context(&context_prefix, context_line);
+            }
+        }
+        if self.colors {
+            println!(
+                "{match_prefix}{}",
+                highlight_spans(line.line.as_str(), spans)
+            );
+        } else {
+            println!("{match_prefix}{}", line.line);
+        }
+        self.pending = Some(PendingContext {
+            log_path: log_path.to_path_buf(),
+            match_offset: offset,
+            end_offset: line.end_offset,
+            after: line.after,
+            context_prefix,
+        });
+    }
 
-    let mut result = String::new();
-    let mut i = 0;
+    fn print_context(&self, context_prefix: &str, line: String) {
+        let line = if self.colors { line.b_grey() } else { line };
+        println!("{context_prefix}{line}");
+    }
 
-    while i < line.len() {
-        let mut matched = None;
+    /// Starts output of file printed under its own heading, its first block has no separator
+    pub fn start_file(&mut self) {
+        self.finish();
+        self.show_separator = false;
+    }
 
-        for (j, word) in lowercase_words.iter().enumerate() {
-            if lowercase_line[i..].starts_with(word) {
-                matched = Some((word.len(), words[j].clone()));
-                break;
+    /// Prints context held back after last matched line
+    pub fn finish(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            for (_, context_line) in pending.after {
+                self.print_context(&pending.context_prefix, context_line);
             }
         }
+    }
+}
 
-        if let Some((len, _)) = matched {
-            let part = line[i..i + len].to_string().red();
-            result += part.as_str();
-            i += len;
-        } else {
-            let c = line[i..].chars().next().unwrap();
-            result.push(c);
-            i += c.len_utf8();
-        }
+/// Context lines after matched line not printed yet
+struct PendingContext {
+    log_path: PathBuf,
+    match_offset: u64,
+    end_offset: u64,
+    after: Vec<(u64, String)>,
+    context_prefix: String,
+}
+fn highlight_spans(line: &str, spans: &[Range<usize>]) -> String {
+    let mut result = String::new();
+    let mut i = 0;
+    for span in spans {
+        result += &line[i..span.start];
+        result += line[span.clone()].to_string().red().as_str();
+        i = span.end;
     }
+    result += &line[i..];
+    result
+}
 
+fn json_strings(values: &[(u64, String)]) -> String {
+    let values: Vec<String> = values.iter().map(|(_, x)| json_string(x)).collect();
+    format!("[{}]", values.join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            '\n' => result += "\\n",
+            '\r' => result += "\\r",
+            '\t' => result += "\\t",
+            c if (c as u32) < 0x20 => result += &format!("\\u{:04x}", c as u32),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
     result
 }
@@ -0,0 +1,401 @@
+mod parser;
+
+use parser::QueryParser;
+use std::ops::Range;
+
+pub enum Query {
+    Word(String),
+    Any(Vec<Query>),
+    All(Vec<Query>),
+    Phrase(Vec<String>),
+    /// Two words within distance (in words), any order
+    Near(String, String, usize),
+    /// Excludes lines matching query
+    Not(Box<Query>),
+    /// Words within edit distance
+    Fuzzy(String, usize),
+    /// Words matching pattern with `*` wildcards
+    Wildcard(String),
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct QueryOptions {
+    pub whole_words: bool,
+    pub order_important: bool,
+    /// Max edit distance for all words, 0 disables fuzzy matching
+    pub fuzzy: usize,
+    /// Iterate matched lines from the end of log
+    pub reverse: bool,
+}
+
+impl QueryOptions {
+    pub fn with_fuzzy(self, fuzzy: usize) -> Self {
+        Self {
+            fuzzy: self.fuzzy.max(fuzzy),
+            ..self
+        }
+    }
+}
+
+fn reduce(queries: Vec<Query>, combine: impl FnOnce(Vec<Query>) -> Query) -> Option<Query> {
+    match queries.len() {
+        0 => None,
+        1 => queries.into_iter().next(),
+        _ => Some(combine(queries)),
+    }
+}
+
+impl Query {
+    pub fn parse(query_str: &str) -> anyhow::Result<Option<Self>> {
+        QueryParser::parse(query_str)
+    }
+
+    pub fn with_word(word: String) -> Self {
+        Self::Word(word)
+    }
+
+    pub fn with_phrase(mut words: Vec<String>) -> Option<Self> {
+        match words.len() {
+            0 => None,
+            1 => words.pop().map(Self::Word),
+            _ => Some(Self::Phrase(words)),
+        }
+    }
+
+    pub fn with_fuzzy(word: String, distance: usize) -> Self {
+        Self::Fuzzy(word, distance)
+    }
+
+    pub fn with_wildcard(pattern: String) -> Self {
+        Self::Wildcard(pattern)
+    }
+
+    pub fn with_near(first: String, second: String, distance: usize) -> Self {
+        Self::Near(first, second, distance)
+    }
+
+    pub fn with_not(query: Query) -> Self {
+        Self::Not(Box::new(query))
+    }
+
+    pub fn with_any(queries: Vec<Query>) -> Option<Self> {
+        reduce(queries, Self::Any)
+    }
+
+    pub fn with_all(queries: Vec<Query>) -> Option<Self> {
+        reduce(queries, Self::All)
+    }
+
+    /// Returns true if query has phrases or proximity conditions
+    pub fn has_proximity(&self) -> bool {
+        match self {
+            Self::Word(_) | Self::Fuzzy(..) | Self::Wildcard(_) => false,
+            Self::Phrase(_) | Self::Near(..) => true,
+            Self::Not(query) => query.has_proximity(),
+            Self::Any(queries) | Self::All(queries) => queries.iter().any(Self::has_proximity),
+        }
+    }
+
+    /// Evaluates whole query against line `words`.
+    pub fn check_line(&self, words: &[String], options: QueryOptions) -> bool {
+        match self {
+            Self::Word(word) => word_positions(word, words, options).next().is_some(),
+            Self::Fuzzy(word, distance) => {
+                let options = options.with_fuzzy(*distance);
+                word_positions(word, words, options).next().is_some()
+            }
+            Self::Wildcard(pattern) => words.iter().any(|x| wildcard_matches(x, pattern)),
+            Self::Not(query) => !query.check_line(words, options),
+            Self::Any(queries) => queries.iter().any(|query| query.check_line(words, options)),
+            Self::All(queries) => queries.iter().all(|query| query.check_line(words, options)),
+            Self::Phrase(phrase) => phrase_position(phrase, words, options).is_some(),
+            Self::Near(first, second, distance) => {
+                let second_positions = word_positions(second, words, options);
+                word_positions(first, words, options).any(|first| {
+                    second_positions
+                        .clone()
+                        .any(|second| first.abs_diff(second) <= *distance)
+                })
+            }
+        }
+    }
+
+    pub fn check_words_order(&self, words: &[String]) -> bool {
+        match self {
+            Self::Word(_)
+            | Self::Phrase(_)
+            | Self::Near(..)
+            | Self::Not(_)
+            | Self::Fuzzy(..)
+            | Self::Wildcard(_) => true,
+            Self::Any(queries) => queries.iter().any(|query| query.check_words_order(words)),
+            Self::All(queries) => {
+                let mut prev_index = -1isize;
+                for query in queries {
+                    match query {
+                        Self::Word(word) => {
+                            if let Some(index) = words.iter().position(|w| w.starts_with(word)) {
+                                if (index as isize) < prev_index {
+                                    return false;
+                                }
+                                prev_index = index as isize;
+                            }
+                        }
+                        Self::Phrase(phrase) => {
+                            if let Some(index) =
+                                phrase_position(phrase, words, QueryOptions::default())
+                            {
+                                if (index as isize) < prev_index {
+                                    return false;
+                                }
+                                prev_index = index as isize;
+                            }
+                        }
+                        Self::Any(queries) => {
+                            let mut min_index = None;
+                            for query in queries {
+                                if let Self::Word(word) = query
+                                    && let Some(index) = words.iter().position(|w| w == word)
+                                {
+                                    if let Some(min_index) = &mut min_index {
+                                        if index < *min_index {
+                                            *min_index = index;
+                                        }
+                                    } else {
+                                        min_index = Some(index);
+                                    }
+                                    if (index as isize) < prev_index {
+                                        return false;
+                                    }
+                                }
+                            }
+                            if let Some(index) = min_index {
+                                if (index as isize) < prev_index {
+                                    return false;
+                                }
+                                prev_index = index as isize;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    /// Turns top level conjunction into disjunction of its terms, exclusions are kept
+    pub fn into_any_terms(self) -> Self {
+        let Self::All(queries) = self else {
+            return self;
+        };
+        let (mut excluded, included): (Vec<_>, Vec<_>) =
+            queries.into_iter().partition(|x| matches!(x, Self::Not(_)));
+        match Self::with_any(included) {
+            Some(included) if excluded.is_empty() => included,
+            Some(included) => {
+                excluded.insert(0, included);
+                Self::All(excluded)
+            }
+            None => Self::All(excluded),
+        }
+    }
+
+    /// Top level terms of query which are scored separately by relevance ranking
+    pub fn terms(&self) -> Vec<&Self> {
+        match self {
+            Self::Any(queries) => queries
+                .iter()
+                .filter(|x| !matches!(x, Self::Not(_)))
+                .collect(),
+            Self::All(queries) => queries.iter().flat_map(Self::terms).collect(),
+            Self::Not(_) => Vec::new(),
+            _ => vec![self],
+        }
+    }
+
+    /// Returns true if line token is one of query terms, excluded terms don't count
+    pub fn matches_token(&self, token: &str, options: QueryOptions) -> bool {
+        match self {
+            Self::Word(word) => word_matches(token, word, options),
+            Self::Fuzzy(word, distance) => word_matches(token, word, options.with_fuzzy(*distance)),
+            Self::Wildcard(pattern) => wildcard_matches(token, pattern),
+            Self::Phrase(phrase) => phrase.iter().any(|x| word_matches(token, x, options)),
+            Self::Near(first, second, _) => {
+                word_matches(token, first, options) || word_matches(token, second, options)
+            }
+            Self::Not(_) => false,
+            Self::Any(queries) | Self::All(queries) => {
+                queries.iter().any(|x| x.matches_token(token, options))
+            }
+        }
+    }
+}
+
+fn word_matches(word: &str, query_word: &str, options: QueryOptions) -> bool {
+    word == query_word
+        || !options.whole_words && word.starts_with(query_word)
+        || options.fuzzy > 0 && edit_distance(word, query_word) <= options.fuzzy
+}
+
+fn word_positions<'a>(
+    query_word: &'a str,
+    words: &'a [String],
+    options: QueryOptions,
+) -> impl Iterator<Item = usize> + Clone + 'a {
+    words
+        .iter()
+        .enumerate()
+        .filter(move |(_, w)| word_matches(w, query_word, options))
+        .map(|(i, _)| i)
+}
+
+fn phrase_position(phrase: &[String], words: &[String], options: QueryOptions) -> Option<usize> {
+    words.windows(phrase.len()).position(|window| {
+        window
+            .iter()
+            .zip(phrase)
+            .all(|(w, p)| word_matches(w, p, options))
+    })
+}
+
+/// Matches text against pattern with `*` wildcards
+pub fn wildcard_matches(text: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcards
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Levenshtein distance with transpositions (optimal string alignment)
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 0..a.len() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for j in 0..b.len() {
+            let substitution = rows[i][j] + usize::from(a[i] != b[j]);
+            row[j + 1] = substitution.min(rows[i][j + 1] + 1).min(row[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                row[j + 1] = row[j + 1].min(rows[i - 1][j - 1] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+pub fn parse_words(line: &str) -> Vec<String> {
+    parse_word_spans(line)
+        .into_iter()
+        .map(|(_, word)| word)
+        .collect()
+}
+
+/// Same words as `parse_words` with their byte ranges within line
+pub fn parse_word_spans(line: &str) -> Vec<(Range<usize>, String)> {
+    let mut words = Vec::new();
+    // Word start is always a suffix of line
+    let span = |word: &str, word_start: &str| {
+        let start = line.len() - word_start.len();
+        start..start + word.len()
+    };
+    let mut word_start = skip_to_word_start(line);
+    while !word_start.is_empty() {
+        if let Some((word, rest)) = word_start.split_once(|c: char| !c.is_alphabetic()) {
+            if word.len() > 1 {
+                words.push((span(word, word_start), word.to_uppercase()));
+            }
+            word_start = skip_to_word_start(rest);
+        } else {
+            if word_start.len() > 1 {
+                words.push((span(word_start, word_start), word_start.to_uppercase()));
+            }
+            break;
+        }
+    }
+    words
+}
+
+fn skip_to_word_start(input: &str) -> &str {
+    let mut rest = input;
+    while !rest.is_empty() {
+        rest = if let Some(rest) = strip_ansi_ctrl(rest) {
+            rest
+        } else if let Some(rest) = strip_long_hex(rest) {
+            rest
+        } else if let Some(rest) = strip_first_char(rest, |c: char| !c.is_alphabetic()) {
+            rest
+        } else {
+            break;
+        };
+    }
+    rest
+}
+
+fn strip_first_char(s: &str, predicate: impl Fn(char) -> bool) -> Option<&str> {
+    if let Some(first) = s.chars().next()
+        && predicate(first)
+    {
+        return Some(&s[first.len_utf8()..]);
+    }
+    None
+}
+
+fn strip_long_hex(s: &str) -> Option<&str> {
+    if let Some((hex, rest)) = s.split_once(|c: char| !c.is_ascii_hexdigit())
+        && hex.len() > 8
+    {
+        return Some(rest);
+    }
+    None
+}
+
+fn strip_ansi_ctrl(s: &str) -> Option<&str> {
+    let rest = if let Some(rest) = s.strip_prefix("\x1b[") {
+        rest
+    } else {
+        s.strip_prefix("\u{1b}[")?
+    };
+    rest.strip_prefix(|c: char| c.is_ascii_lowercase() || c.is_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, line: &str) -> bool {
+        let query = Query::parse(query).unwrap().unwrap();
+        query.check_line(&parse_words(line), QueryOptions::default())
+    }
+
+    #[test]
+    fn phrase_inside_any() {
+        let query = r#""write to db" | unfailing"#;
+        assert!(matches(query, "failed write to db"));
+        assert!(matches(query, "unfailing write"));
+        assert!(!matches(query, "failed write to the db"));
+    }
+
+    #[test]
+    fn excluded_phrase_inside_any() {
+        let query = r#"unfailing | -"connection refused""#;
+        assert!(matches(query, "connection was refused"));
+        assert!(matches(query, "unfailing connection refused"));
+        assert!(!matches(query, "connection refused retrying"));
+    }
+}
@@ -0,0 +1,405 @@
+// Query grammar (operators from lowest to highest precedence):
+//   or      := and (("|" | "OR") and)*
+//   and     := unary ("AND"? unary)*
+//   unary   := ("NOT" | "-") unary | near
+//   near    := primary (("NEAR" | "NEAR/distance") primary)*
+//   primary := word | word "~" distance? | wildcard | "\"" phrase "\"" | "(" or ")"
+//   wildcard := letters with "*" (like "*timeout" or "conn*refused")
+
+use crate::query::{Query, parse_words};
+
+const DEFAULT_NEAR_DISTANCE: usize = 5;
+const DEFAULT_FUZZY_DISTANCE: usize = 1;
+
+#[derive(PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Or,
+    And,
+    Not,
+    Near(usize),
+    Open,
+    Close,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Self::Word(word) => format!("'{word}'"),
+            Self::Phrase(phrase) => format!("'\"{phrase}\"'"),
+            Self::Or => "'|'".to_string(),
+            Self::And => "'AND'".to_string(),
+            Self::Not => "'NOT'".to_string(),
+            Self::Near(distance) => format!("'NEAR/{distance}'"),
+            Self::Open => "'('".to_string(),
+            Self::Close => "')'".to_string(),
+        }
+    }
+}
+
+pub struct QueryParser<'a> {
+    query_str: &'a str,
+    /// Tokens with byte positions in query string
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl<'a> QueryParser<'a> {
+    pub fn parse(query_str: &'a str) -> anyhow::Result<Option<Query>> {
+        let mut parser = Self {
+            query_str,
+            tokens: Vec::new(),
+            pos: 0,
+        };
+        parser.tokenize()?;
+        if parser.tokens.is_empty() {
+            return Ok(None);
+        }
+        let query = parser.parse_or()?;
+        if let Some((token, position)) = parser.tokens.get(parser.pos) {
+            return Err(parser.error(*position, &format!("Unexpected {}", token.describe())));
+        }
+        Ok(query)
+    }
+
+    fn tokenize(&mut self) -> anyhow::Result<()> {
+        let mut chars = self.query_str.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let token = match c {
+                c if c.is_whitespace() => continue,
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '|' => Token::Or,
+                '-' => Token::Not,
+                '"' => {
+                    let Some(len) = self.query_str[start + 1..].find('"') else {
+                        return Err(self.error(start, "Unterminated phrase"));
+                    };
+                    while chars.next_if(|(i, _)| *i <= start + 1 + len).is_some() {}
+                    Token::Phrase(self.query_str[start + 1..start + 1 + len].to_string())
+                }
+                _ => {
+                    let mut end = start + c.len_utf8();
+                    while let Some((i, c)) = chars.next_if(|(_, c)| !is_delimiter(*c)) {
+                        end = i + c.len_utf8();
+                    }
+                    self.word_token(&self.query_str[start..end], start)?
+                }
+            };
+            self.tokens.push((token, start));
+        }
+        Ok(())
+    }
+
+    fn word_token(&self, word: &str, position: usize) -> anyhow::Result<Token> {
+        Ok(match word {
+            "OR" => Token::Or,
+            "AND" => Token::And,
+            "NOT" => Token::Not,
+            "NEAR" => Token::Near(DEFAULT_NEAR_DISTANCE),
+            _ => match word.strip_prefix("NEAR/") {
+                Some(distance) => Token::Near(
+                    distance
+                        .parse()
+                        .map_err(|_| self.error(position, "Invalid NEAR distance"))?,
+                ),
+                None => Token::Word(word.to_string()),
+            },
+        })
+    }
+
+    fn parse_or(&mut self) -> anyhow::Result<Option<Query>> {
+        let mut any = Vec::new();
+        any.extend(self.parse_and()?);
+        while self.next_if(|token| *token == Token::Or).is_some() {
+            any.extend(self.parse_and()?);
+        }
+        Ok(Query::with_any(any))
+    }
+
+    fn parse_and(&mut self) -> anyhow::Result<Option<Query>> {
+        let mut all = Vec::new();
+        all.extend(self.parse_unary()?);
+        loop {
+            match self.peek() {
+                None | Some(Token::Or) | Some(Token::Close) => break,
+                Some(Token::And) => {
+                    self.pos += 1;
+                }
+                _ => {}
+            }
+            all.extend(self.parse_unary()?);
+        }
+        Ok(Query::with_all(all))
+    }
+
+    fn parse_unary(&mut self) -> anyhow::Result<Option<Query>> {
+        if self.next_if(|token| *token == Token::Not).is_some() {
+            return Ok(self.parse_unary()?.map(Query::with_not));
+        }
+        self.parse_near()
+    }
+
+    fn parse_near(&mut self) -> anyhow::Result<Option<Query>> {
+        let first = self.parse_primary()?;
+        let mut last_word = match &first {
+            Some(Query::Word(word)) => Some(word.clone()),
+            _ => None,
+        };
+        let mut near = Vec::new();
+        while let Some(&(Token::Near(distance), position)) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            match (last_word.take(), self.parse_primary()?) {
+                (Some(first), Some(Query::Word(second))) => {
+                    near.push(Query::with_near(first, second.clone(), distance));
+                    last_word = Some(second);
+                }
+                _ => return Err(self.error(position, "NEAR requires single words on both sides")),
+            }
+        }
+        Ok(if near.is_empty() {
+            first
+        } else {
+            Query::with_all(near)
+        })
+    }
+
+    fn parse_primary(&mut self) -> anyhow::Result<Option<Query>> {
+        let Some((token, position)) = self.tokens.get(self.pos) else {
+            return Err(self.error(self.query_str.len(), "Unexpected end of query"));
+        };
+        let position = *position;
+        let query = match token {
+            Token::Word(word) if word.contains('*') => Some(self.wildcard_query(word, position)?),
+            Token::Word(word) => match word.rsplit_once('~') {
+                Some((word, distance)) => self.fuzzy_query(word, distance, position)?,
+                None => Query::with_all(
+                    parse_words(word)
+                        .into_iter()
+                        .map(Query::with_word)
+                        .collect(),
+                ),
+            },
+            Token::Phrase(phrase) => Query::with_phrase(parse_words(phrase)),
+            Token::Open => {
+                self.pos += 1;
+                let query = self.parse_or()?;
+                if self.next_if(|token| *token == Token::Close).is_none() {
+                    let position = self.current_position();
+                    return Err(self.error(position, "Expected ')'"));
+                }
+                return Ok(query);
+            }
+            token => {
+                let message = format!("Expected word or phrase, found {}", token.describe());
+                return Err(self.error(position, &message));
+            }
+        };
+        self.pos += 1;
+        Ok(query)
+    }
+
+    fn fuzzy_query(
+        &self,
+        word: &str,
+        distance: &str,
+        position: usize,
+    ) -> anyhow::Result<Option<Query>> {
+        let distance = if distance.is_empty() {
+            DEFAULT_FUZZY_DISTANCE
+        } else {
+            distance
+                .parse()
+                .map_err(|_| self.error(position, "Invalid fuzzy distance"))?
+        };
+        let mut words = parse_words(word);
+        match words.len() {
+            0 => Ok(None),
+            1 => Ok(words.pop().map(|word| Query::with_fuzzy(word, distance))),
+            _ => Err(self.error(position, "Fuzzy term must be a single word")),
+        }
+    }
+
+    fn wildcard_query(&self, pattern: &str, position: usize) -> anyhow::Result<Query> {
+        if !pattern.chars().all(|c| c == '*' || c.is_alphabetic()) {
+            return Err(self.error(position, "Wildcard term can contain only letters and '*'"));
+        }
+        if !pattern.chars().any(char::is_alphabetic) {
+            return Err(self.error(position, "Wildcard term must contain letters"));
+        }
+        Ok(Query::with_wildcard(pattern.to_uppercase()))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    /// Consumes token matching predicate, returns its position
+    fn next_if(&mut self, predicate: impl Fn(&Token) -> bool) -> Option<usize> {
+        let (token, position) = self.tokens.get(self.pos)?;
+        if predicate(token) {
+            self.pos += 1;
+            Some(*position)
+        } else {
+            None
+        }
+    }
+
+    fn current_position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.query_str.len(), |(_, position)| *position)
+    }
+
+    fn error(&self, position: usize, message: &str) -> anyhow::Error {
+        let column = self.query_str[..position].chars().count();
+        anyhow::anyhow!(
+            "{message} at position {}:\n  {}\n  {}^",
+            column + 1,
+            self.query_str,
+            " ".repeat(column)
+        )
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '|' | '"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Query as s-expression, words are uppercased by parser
+    fn show(query: &Query) -> String {
+        let list = |name: &str, queries: &[Query]| {
+            let queries: Vec<String> = queries.iter().map(show).collect();
+            format!("({name} {})", queries.join(" "))
+        };
+        match query {
+            Query::Word(word) => word.clone(),
+            Query::Any(queries) => list("or", queries),
+            Query::All(queries) => list("and", queries),
+            Query::Phrase(words) => format!("\"{}\"", words.join(" ")),
+            Query::Near(first, second, distance) => format!("(near/{distance} {first} {second})"),
+            Query::Not(query) => format!("(not {})", show(query)),
+            Query::Fuzzy(word, distance) => format!("{word}~{distance}"),
+            Query::Wildcard(pattern) => pattern.clone(),
+        }
+    }
+
+    fn parse(query: &str) -> String {
+        QueryParser::parse(query)
+            .unwrap()
+            .as_ref()
+            .map_or(String::new(), show)
+    }
+
+    fn parse_error(query: &str) -> String {
+        let error = QueryParser::parse(query).err().unwrap().to_string();
+        error.lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(parse("aa bb | cc"), "(or (and AA BB) CC)");
+        assert_eq!(parse("aa | bb AND cc"), "(or AA (and BB CC))");
+        assert_eq!(parse("aa OR bb cc"), "(or AA (and BB CC))");
+        assert_eq!(parse("NOT aa bb"), "(and (not AA) BB)");
+        assert_eq!(parse("NOT aa NEAR/1 bb"), "(not (near/1 AA BB))");
+        assert_eq!(parse("aa NEAR bb cc"), "(and (near/5 AA BB) CC)");
+        assert_eq!(
+            parse("aa NEAR/2 bb NEAR/3 cc"),
+            "(and (near/2 AA BB) (near/3 BB CC))"
+        );
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(parse("(aa | bb) cc"), "(and (or AA BB) CC)");
+        assert_eq!(parse("aa (bb | (cc dd))"), "(and AA (or BB (and CC DD)))");
+        assert_eq!(parse("-(aa | bb)"), "(not (or AA BB))");
+    }
+
+    #[test]
+    fn implicit_and() {
+        assert_eq!(parse("aa bb cc"), "(and AA BB CC)");
+        assert_eq!(parse("aa AND bb"), "(and AA BB)");
+        assert_eq!(parse("foo.bar"), "(and FOO BAR)");
+        assert_eq!(
+            parse("\"write to db\" failed"),
+            "(and \"WRITE TO DB\" FAILED)"
+        );
+    }
+
+    #[test]
+    fn not_and_minus() {
+        assert_eq!(parse("-aa"), "(not AA)");
+        assert_eq!(parse("NOT aa"), "(not AA)");
+        assert_eq!(parse("aa -bb"), "(and AA (not BB))");
+        assert_eq!(parse("aa -\"bb cc\""), "(and AA (not \"BB CC\"))");
+        assert_eq!(parse("NOT NOT aa"), "(not (not AA))");
+        assert_eq!(parse("aa-bb"), "(and AA BB)");
+        // Lowercase operators are words
+        assert_eq!(parse("not aa or bb"), "(and NOT AA OR BB)");
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(parse("receive~"), "RECEIVE~1");
+        assert_eq!(parse("receive~2"), "RECEIVE~2");
+        assert_eq!(parse("aa | bb~2"), "(or AA BB~2)");
+    }
+
+    #[test]
+    fn wildcard() {
+        assert_eq!(parse("*timeout"), "*TIMEOUT");
+        assert_eq!(parse("conn*refused"), "CONN*REFUSED");
+        assert_eq!(parse("-*timeout*"), "(not *TIMEOUT*)");
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(
+            parse_error("(timeout | refused"),
+            "Expected ')' at position 19:"
+        );
+        assert_eq!(parse_error("aa )"), "Unexpected ')' at position 4:");
+        assert_eq!(
+            parse_error("aa |"),
+            "Unexpected end of query at position 5:"
+        );
+        assert_eq!(parse_error("aa \"bb"), "Unterminated phrase at position 4:");
+        assert_eq!(
+            parse_error("aa NEAR/x bb"),
+            "Invalid NEAR distance at position 4:"
+        );
+        assert_eq!(
+            parse_error("aa NEAR \"bb cc\""),
+            "NEAR requires single words on both sides at position 4:"
+        );
+        assert_eq!(
+            parse_error("aa bb~x"),
+            "Invalid fuzzy distance at position 4:"
+        );
+        assert_eq!(
+            parse_error("foo.bar~"),
+            "Fuzzy term must be a single word at position 1:"
+        );
+        assert_eq!(
+            parse_error("aa conn*1"),
+            "Wildcard term can contain only letters and '*' at position 4:"
+        );
+        assert_eq!(
+            parse_error("aa AND OR"),
+            "Expected word or phrase, found '|' at position 8:"
+        );
+        assert_eq!(
+            parse_error("()"),
+            "Expected word or phrase, found ')' at position 2:"
+        );
+        // Position counts characters, not bytes
+        assert_eq!(parse_error("ää )"), "Unexpected ')' at position 4:");
+    }
+}
@@ -0,0 +1,54 @@
+use crate::index::{IxReader, LinesReader};
+use crate::query::{Query, QueryOptions};
+
+/// BM25 term frequency saturation
+const K1: f64 = 1.2;
+
+/// Returns matched lines sorted by relevance (BM25 over query terms), most relevant first.
+/// Term readers are walked along with matched lines to find terms of each line.
+/// Term frequency is number of term positions within line if index has positions, otherwise 1.
+/// Index doesn't store line lengths, so scores are not normalized by length (BM25 with b = 0).
+pub fn rank_lines(
+    ix: &IxReader,
+    lines: &mut LinesReader,
+    terms: &[&Query],
+    options: QueryOptions,
+) -> anyhow::Result<Vec<(u64, f64)>> {
+    let mut readers = Vec::with_capacity(terms.len());
+    let mut current = Vec::with_capacity(terms.len());
+    for term in terms {
+        let mut reader = ix.query(term, options)?;
+        current.push(reader.next()?);
+        readers.push(reader);
+    }
+    let lines_count = ix.lines_count as usize;
+    let weights: Vec<f64> = readers
+        .iter()
+        .map(|x| idf(x.lines_count().min(lines_count), lines_count))
+        .collect();
+
+    let mut ranked = Vec::new();
+    while let Some(offset) = lines.next()? {
+        let mut score = 0.0;
+        for (i, reader) in readers.iter_mut().enumerate() {
+            while let Some(term_offset) = current[i]
+                && term_offset < offset
+            {
+                current[i] = reader.next()?;
+            }
+            if current[i] == Some(offset) {
+                let frequency = reader.positions().len().max(1) as f64;
+                score += weights[i] * frequency * (K1 + 1.0) / (frequency + K1);
+            }
+        }
+        ranked.push((offset, score));
+    }
+    // Stable sort keeps file order for equal scores
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(ranked)
+}
+
+fn idf(lines_with_term: usize, lines_count: usize) -> f64 {
+    let n = lines_with_term as f64;
+    ((lines_count as f64 - n + 0.5) / (n + 0.5) + 1.0).ln()
+}
@@ -0,0 +1,66 @@
+use crate::check_index;
+use crate::index::{IxOptions, IxReader};
+use crate::path_utils::resolve_log_files;
+use clap::{Args, ValueEnum};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct TermsArgs {
+    /// Path to log file or directory
+    #[arg(short, long)]
+    path: PathBuf,
+
+    /// Show only terms starting with prefix
+    prefix: Option<String>,
+
+    /// Sort order
+    #[arg(short, long, value_enum, default_value = "count")]
+    sort: TermsSort,
+
+    /// Show only first N terms (0 for all)
+    #[arg(short = 'n', long, default_value = "0")]
+    limit: usize,
+
+    #[arg(short, long, default_value = "false")]
+    force_reindex: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TermsSort {
+    /// Most frequent first
+    Count,
+    /// Alphabetical
+    Name,
+}
+
+/// Prints indexed terms with number of lines containing them (summed for all files)
+pub fn run_terms(args: &TermsArgs) -> anyhow::Result<()> {
+    let prefix = args.prefix.as_deref().unwrap_or_default().to_uppercase();
+    let mut counts = BTreeMap::<String, usize>::new();
+    for log_path in resolve_log_files(vec![args.path.clone()])? {
+        check_index(log_path.clone(), args.force_reindex, IxOptions::default())?;
+        let ix = IxReader::new(log_path)?;
+        for (term, count) in ix.lines_counts(&prefix) {
+            *counts.entry(term).or_default() += count;
+        }
+    }
+    let mut terms: Vec<(String, usize)> = counts.into_iter().collect();
+    if let TermsSort::Count = args.sort {
+        terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    }
+    if args.limit > 0 {
+        terms.truncate(args.limit);
+    }
+    let count_width = terms
+        .iter()
+        .map(|x| x.1)
+        .max()
+        .unwrap_or(0)
+        .to_string()
+        .len();
+    for (term, count) in terms {
+        println!("{count:>count_width$} {term}");
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+static TIMESTAMP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(\d{4})-(\d{2})-(\d{2})(?:[T ](\d{2}):(\d{2})(?::(\d{2})(?:[.,](\d{1,9}))?)?)?")
+        .unwrap()
+});
+
+/// Max distance from line start to timestamp
+const TIMESTAMP_SEARCH_LEN: usize = 64;
+
+/// Parses first timestamp like `2025-03-27T12:15:38.105089Z` or `2025-03-27 12:15` near line
+/// start, returns seconds since unix epoch. Time zones are ignored.
+pub fn parse_line_timestamp(line: &str) -> Option<i64> {
+    parse_line_timestamp_nanos(line).map(|(seconds, _)| seconds)
+}
+
+/// Same as [`parse_line_timestamp`] with fraction of second, returns (seconds, nanoseconds)
+pub fn parse_line_timestamp_nanos(line: &str) -> Option<(i64, u32)> {
+    let mut end = line.len().min(TIMESTAMP_SEARCH_LEN);
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    parse_timestamp_nanos(&line[..end])
+}
+
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    parse_timestamp_nanos(s).map(|(seconds, _)| seconds)
+}
+
+fn parse_timestamp_nanos(s: &str) -> Option<(i64, u32)> {
+    let captures = TIMESTAMP.captures(s)?;
+    let field = |i: usize| -> i64 {
+        captures
+            .get(i)
+            .and_then(|x| x.as_str().parse().ok())
+            .unwrap_or(0)
+    };
+    let days = days_from_civil(field(1), field(2), field(3));
+    // Fraction digits are padded to nanoseconds: `.105` is 105000000
+    let nanos = captures.get(7).map_or(0, |x| {
+        let digits = x.as_str();
+        digits.parse::<u32>().unwrap_or(0) * 10u32.pow(9 - digits.len() as u32)
+    });
+    Some((
+        days * 86400 + field(4) * 3600 + field(5) * 60 + field(6),
+        nanos,
+    ))
+}
+
+/// Parses duration like `30s`, `5m`, `1h`, `1d` (seconds if no unit)
+pub fn parse_duration(s: &str) -> anyhow::Result<i64> {
+    let (value, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let value: i64 = value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration: {s}"))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => anyhow::bail!("Invalid duration unit: {s}"),
+    };
+    if value == 0 {
+        anyhow::bail!("Duration must be positive: {s}");
+    }
+    Ok(value * seconds)
+}
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    let (y, m, d) = civil_from_days(timestamp.div_euclid(86400));
+    let seconds = timestamp.rem_euclid(86400);
+    format!(
+        "{y:04}-{m:02}-{d:02} {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+// Algorithms from http://howardhinnant.github.io/date_algorithms.html
+
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (
+        if m <= 2 {
+            yoe + era * 400 + 1
+        } else {
+            yoe + era * 400
+        },
+        m,
+        d,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fraction_of_second() {
+        let (seconds, nanos) = parse_line_timestamp_nanos("2025-03-27T12:15:39.5Z INFO").unwrap();
+        assert_eq!(seconds, parse_timestamp("2025-03-27T12:15:39").unwrap());
+        assert_eq!(nanos, 500_000_000);
+        let (_, nanos) = parse_line_timestamp_nanos("2025-03-27 12:15:39,105089 INFO").unwrap();
+        assert_eq!(nanos, 105_089_000);
+        let (_, nanos) = parse_line_timestamp_nanos("2025-03-27 12:15 INFO").unwrap();
+        assert_eq!(nanos, 0);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::query::Query;
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+pub fn parse_trigrams(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    let mut trigrams = Vec::new();
+    for window in chars.windows(3) {
+        if !window.iter().any(|c| *c == '\n' || *c == '\r') {
+            trigrams.push(window.iter().collect());
+        }
+    }
+    trigrams
+}
+
+/// Returns trigrams query that must be satisfied by any line containing `substring`.
+/// `None` means that index can't narrow candidates and all lines must be checked.
+pub fn substring_query(substring: &str) -> Option<Query> {
+    let mut trigrams = parse_trigrams(substring);
+    trigrams.sort();
+    trigrams.dedup();
+    Query::with_all(trigrams.into_iter().map(Query::with_word).collect())
+}
+
+/// Returns trigrams query that must be satisfied by any line matching `pattern`.
+/// `None` means that index can't narrow candidates and all lines must be checked.
+pub fn regex_query(pattern: &str) -> anyhow::Result<Option<Query>> {
+    let hir = regex_syntax::Parser::new().parse(pattern)?;
+    Ok(hir_query(&hir).required)
+}
+
+struct HirTrigrams {
+    /// Set when node matches exactly this literal text
+    literal: Option<String>,
+    required: Option<Query>,
+}
+
+impl HirTrigrams {
+    fn none() -> Self {
+        Self {
+            literal: None,
+            required: None,
+        }
+    }
+
+    fn literal(text: String) -> Self {
+        Self {
+            required: substring_query(&text),
+            literal: Some(text),
+        }
+    }
+}
+
+fn hir_query(hir: &Hir) -> HirTrigrams {
+    match hir.kind() {
+        HirKind::Literal(literal) => {
+            HirTrigrams::literal(String::from_utf8_lossy(&literal.0).to_string())
+        }
+        HirKind::Class(class) => match class_char(class) {
+            Some(c) => HirTrigrams::literal(c.to_string()),
+            None => HirTrigrams::none(),
+        },
+        HirKind::Capture(capture) => hir_query(&capture.sub),
+        HirKind::Repetition(repetition) if repetition.min > 0 => HirTrigrams {
+            literal: None,
+            required: hir_query(&repetition.sub).required,
+        },
+        HirKind::Concat(subs) => {
+            let mut literal = Some(String::new());
+            let mut run = String::new();
+            let mut required = Vec::new();
+            for sub in subs {
+                let sub = hir_query(sub);
+                match sub.literal {
+                    Some(text) => {
+                        run.push_str(&text);
+                        if let Some(literal) = &mut literal {
+                            literal.push_str(&text);
+                        }
+                    }
+                    None => {
+                        literal = None;
+                        required.extend(substring_query(&run));
+                        run.clear();
+                        required.extend(sub.required);
+                    }
+                }
+            }
+            required.extend(substring_query(&run));
+            HirTrigrams {
+                literal,
+                required: Query::with_all(required),
+            }
+        }
+        HirKind::Alternation(subs) => {
+            let mut required = Vec::new();
+            for sub in subs {
+                match hir_query(sub).required {
+                    Some(query) => required.push(query),
+                    None => return HirTrigrams::none(),
+                }
+            }
+            HirTrigrams {
+                literal: None,
+                required: Query::with_any(required),
+            }
+        }
+        _ => HirTrigrams::none(),
+    }
+}
+
+/// Returns the char if class matches only case variants of a single char (like `(?i)a`)
+fn class_char(class: &Class) -> Option<char> {
+    let mut chars = Vec::new();
+    match class {
+        Class::Unicode(class) => {
+            for range in class.ranges() {
+                for c in range.start()..=range.end() {
+                    if chars.len() >= 4 {
+                        return None;
+                    }
+                    chars.push(c);
+                }
+            }
+        }
+        Class::Bytes(class) => {
+            for range in class.ranges() {
+                for b in range.start()..=range.end() {
+                    if chars.len() >= 4 || !b.is_ascii() {
+                        return None;
+                    }
+                    chars.push(b as char);
+                }
+            }
+        }
+    }
+    let first = chars.first()?.to_lowercase().to_string();
+    chars[1..]
+        .iter()
+        .all(|c| c.to_lowercase().to_string() == first)
+        .then(|| chars[0])
+}
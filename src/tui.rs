@@ -0,0 +1,364 @@
+use crate::check_index;
+use crate::filter::LineFilter;
+use crate::index::{IxOptions, IxReader};
+use crate::match_spans;
+use crate::path_utils::resolve_log_files;
+use crate::query::{Query, QueryOptions};
+use clap::Args;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Query results are cut at this number of lines to keep typing responsive
+const MAX_RESULTS: usize = 1000;
+const MAX_CONTEXT: usize = 50;
+
+#[derive(Args)]
+pub struct TuiArgs {
+    /// Path to log file or directory
+    #[arg(short, long)]
+    path: PathBuf,
+
+    /// Number of lines shown around selected match
+    #[arg(short = 'C', long, default_value = "3")]
+    context: usize,
+
+    #[arg(short, long, default_value = "false")]
+    force_reindex: bool,
+}
+
+/// Runs interactive search over indexed logs until Esc is pressed
+pub fn run_tui(args: &TuiArgs) -> anyhow::Result<()> {
+    let mut sources = Vec::new();
+    for log_path in resolve_log_files(vec![args.path.clone()])? {
+        check_index(log_path.clone(), args.force_reindex, IxOptions::default())?;
+        sources.push(IxReader::new(log_path)?);
+    }
+    let mut state = TuiState::new(sources, args.context.min(MAX_CONTEXT));
+    let mut terminal = ratatui::init();
+    let result = state.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// Matched line of log `source`
+struct Match {
+    source: usize,
+    offset: u64,
+    line: String,
+}
+
+struct TuiState {
+    sources: Vec<IxReader>,
+    query: String,
+    whole_words: bool,
+    any_terms: bool,
+    reverse: bool,
+    /// Only matches of this source are listed
+    file_filter: Option<usize>,
+    context: usize,
+    results: Vec<Match>,
+    truncated: bool,
+    /// Dictionary terms of last query to highlight in each source
+    terms: Vec<HashSet<String>>,
+    /// Query parse error, results of last valid query are kept
+    error: Option<String>,
+    list_state: ListState,
+}
+
+impl TuiState {
+    fn new(sources: Vec<IxReader>, context: usize) -> Self {
+        let terms = vec![HashSet::new(); sources.len()];
+        Self {
+            sources,
+            query: String::new(),
+            whole_words: false,
+            any_terms: false,
+            reverse: false,
+            file_filter: None,
+            context,
+            results: Vec::new(),
+            truncated: false,
+            terms,
+            error: None,
+            list_state: ListState::default(),
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+        loop {
+            let context = self.selected_context()?;
+            terminal.draw(|frame| self.draw(frame, context))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && !self.handle_key(key)?
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns false when user quits
+    fn handle_key(&mut self, key: KeyEvent) -> anyhow::Result<bool> {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if control => return Ok(false),
+            KeyCode::Char('u') if control => {
+                self.query.clear();
+                self.run_query()?;
+            }
+            KeyCode::Char('w') if control => {
+                self.whole_words = !self.whole_words;
+                self.run_query()?;
+            }
+            KeyCode::Char('a') if control => {
+                self.any_terms = !self.any_terms;
+                self.run_query()?;
+            }
+            KeyCode::Char('r') if control => {
+                self.reverse = !self.reverse;
+                self.run_query()?;
+            }
+            KeyCode::Char('f') if control => {
+                self.file_filter = match self.file_filter {
+                    Some(_) => None,
+                    None => self.selected().map(|x| x.source),
+                };
+                self.run_query()?;
+            }
+            KeyCode::Char(c) if !control => {
+                self.query.push(c);
+                self.run_query()?;
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.run_query()?;
+            }
+            KeyCode::Up => self.list_state.select_previous(),
+            KeyCode::Down => self.list_state.select_next(),
+            KeyCode::PageUp => self.list_state.scroll_up_by(10),
+            KeyCode::PageDown => self.list_state.scroll_down_by(10),
+            KeyCode::Home => self.list_state.select_first(),
+            KeyCode::End => self.list_state.select_last(),
+            KeyCode::Tab => self.jump_to_file(true),
+            KeyCode::BackTab => self.jump_to_file(false),
+            KeyCode::Left => self.context = self.context.saturating_sub(1),
+            KeyCode::Right => self.context = (self.context + 1).min(MAX_CONTEXT),
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Re-runs query against all sources, selection is reset to first match
+    fn run_query(&mut self) -> anyhow::Result<()> {
+        let query = match Query::parse(&self.query) {
+            Ok(query) => query,
+            Err(err) => {
+                self.error = Some(err.to_string());
+                return Ok(());
+            }
+        };
+        self.error = None;
+        self.results.clear();
+        self.truncated = false;
+        let Some(mut query) = query else {
+            self.list_state.select(None);
+            return Ok(());
+        };
+        if self.any_terms {
+            query = query.into_any_terms();
+        }
+        let options = QueryOptions {
+            whole_words: self.whole_words,
+            order_important: false,
+            fuzzy: 0,
+            reverse: self.reverse,
+        };
+        for (i, ix) in self.sources.iter().enumerate() {
+            self.terms[i] = ix.expand_terms(&query, options);
+            if self.truncated || self.file_filter.is_some_and(|x| x != i) {
+                continue;
+            }
+            let mut filters = Vec::new();
+            if query.has_proximity() && !ix.has_positions {
+                filters.push(LineFilter::Proximity(&query, options));
+            }
+            let mut lines = ix.query(&query, options)?;
+            while let Some(offset) = lines.next()? {
+                if self.results.len() >= MAX_RESULTS {
+                    self.truncated = true;
+                    break;
+                }
+                if let Some(line) = ix.read_log(offset, 0, 0, &filters)? {
+                    self.results.push(Match {
+                        source: i,
+                        offset,
+                        line: line.line,
+                    });
+                }
+            }
+        }
+        self.list_state
+            .select((!self.results.is_empty()).then_some(0));
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<&Match> {
+        self.results.get(self.list_state.selected()?)
+    }
+
+    /// Selects first match of next (or previous) file having matches
+    fn jump_to_file(&mut self, forward: bool) {
+        let Some(current) = self.selected().map(|x| x.source) else {
+            return;
+        };
+        let index = if forward {
+            self.results.iter().position(|x| x.source > current)
+        } else {
+            let previous = self
+                .results
+                .iter()
+                .rev()
+                .find(|x| x.source < current)
+                .map(|x| x.source);
+            previous.and_then(|previous| self.results.iter().position(|x| x.source == previous))
+        };
+        if index.is_some() {
+            self.list_state.select(index);
+        }
+    }
+
+    /// Lines around selected match with index of matched line
+    fn selected_context(&self) -> anyhow::Result<Option<(Vec<String>, usize)>> {
+        let Some(selected) = self.selected() else {
+            return Ok(None);
+        };
+        let ix = &self.sources[selected.source];
+        let Some(line) = ix.read_log(selected.offset, self.context, self.context, &[])? else {
+            return Ok(None);
+        };
+        let index = line.before.len();
+        let mut lines: Vec<String> = line.before.into_iter().map(|(_, x)| x).collect();
+        lines.push(line.line);
+        lines.extend(line.after.into_iter().map(|(_, x)| x));
+        Ok(Some((lines, index)))
+    }
+
+    fn draw(&mut self, frame: &mut Frame, context: Option<(Vec<String>, usize)>) {
+        let [input_area, results_area, context_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(self.context as u16 * 2 + 3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let input_title = match &self.error {
+            Some(error) => format!(" Query: {error} "),
+            None => " Query ".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(self.query.as_str()).block(Block::bordered().title(input_title)),
+            input_area,
+        );
+        frame.set_cursor_position((
+            input_area.x + 1 + self.query.chars().count() as u16,
+            input_area.y + 1,
+        ));
+
+        let show_file = self.sources.len() > 1;
+        let items: Vec<ListItem> = self
+            .results
+            .iter()
+            .map(|result| {
+                let terms = &self.terms[result.source];
+                let mut line = highlighted(&result.line, terms);
+                if show_file {
+                    let file = format!("{}:", self.sources[result.source].log_path.display());
+                    line.spans.insert(0, Span::styled(file, Color::Magenta));
+                }
+                ListItem::new(line)
+            })
+            .collect();
+        let results_title = format!(
+            " Matches: {}{} ",
+            self.results.len(),
+            if self.truncated { "+" } else { "" }
+        );
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::bordered().title(results_title))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            results_area,
+            &mut self.list_state,
+        );
+
+        let context_lines: Vec<Line> = match (&context, self.selected()) {
+            (Some((lines, index)), Some(selected)) => lines
+                .iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    if i == *index {
+                        highlighted(line, &self.terms[selected.source])
+                    } else {
+                        Line::styled(line.as_str(), Color::DarkGray)
+                    }
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let context_title = match self.selected() {
+            Some(selected) => format!(
+                " {} @{} ",
+                self.sources[selected.source].log_path.display(),
+                selected.offset
+            ),
+            None => " Context ".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(context_lines).block(Block::bordered().title(context_title)),
+            context_area,
+        );
+
+        let toggle = |name: &str, on: bool| {
+            let style = if on {
+                Style::default().bold().fg(Color::Green)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            Span::styled(name.to_string(), style)
+        };
+        frame.render_widget(
+            Line::from(vec![
+                toggle("^W whole words", self.whole_words),
+                Span::raw("  "),
+                toggle("^A any terms", self.any_terms),
+                Span::raw("  "),
+                toggle("^R newest first", self.reverse),
+                Span::raw("  "),
+                toggle("^F this file", self.file_filter.is_some()),
+                Span::raw("  Tab next file  ←/→ context  ^U clear  Esc quit"),
+            ]),
+            status_area,
+        );
+    }
+}
+
+/// Line with matched terms highlighted
+fn highlighted<'a>(line: &'a str, terms: &HashSet<String>) -> Line<'a> {
+    let mut spans = Vec::new();
+    let mut i = 0;
+    for span in match_spans(line, terms, &[]) {
+        spans.push(Span::raw(&line[i..span.start]));
+        spans.push(Span::styled(&line[span.clone()], Color::Red));
+        i = span.end;
+    }
+    spans.push(Span::raw(&line[i..]));
+    Line::from(spans)
+}